@@ -0,0 +1,27 @@
+//! Times naive recursive Fibonacci, which is dominated by function calls and
+//! variable lookups. Run with `cargo bench`.
+
+extern crate rasp;
+
+use std::time::{Duration, Instant};
+use rasp::{Interpreter, Value};
+
+const RUNS: usize = 10;
+
+fn main() {
+    let interpreter = Interpreter::new();
+    interpreter.eval_str("(defun fib (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))")
+        .unwrap();
+
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            assert_eq!(interpreter.eval_str("(fib 22)"), Ok(Value::Integer(17711)));
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+
+    println!("fib 22: median {:?}, min {:?}, max {:?} over {} runs",
+             times[RUNS / 2], times[0], times[RUNS - 1], RUNS);
+}
@@ -1,3 +1,5 @@
+; Examples of the language features supported by rasp.
+
 (defun func ()
   (println "Hello, world!"))
 
@@ -17,6 +19,10 @@
 (set var 5)
 (f)
 
+(set add-one (lambda (n) (+ n 1)))
+(println "{:?} {}" add-one (add-one var))
+
+;; Required, optional, rest and keyword parameters
 (defun complex (a (b nil) ...rest (:d 2))
   (println "(complex a {} b {} :d {} ... {})" a b d rest))
 (complex 2 "Hi" "Bye" 10 ''or 2 :d 5)
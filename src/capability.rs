@@ -0,0 +1,40 @@
+/// A group of built-in functions that a root scope can be given, so that
+/// untrusted scripts can be denied anything that affects the outside world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Special forms, list functions, equality and `error`.
+    Core,
+    /// Arithmetic and numeric comparison.
+    Math,
+    /// Functions on strings.
+    Strings,
+    /// Printing to standard output and reading standard input.
+    Io,
+    /// Reading and writing files.
+    Filesystem,
+    /// `exit` and environment variables.
+    Process,
+    /// The current time and sleeping.
+    Time
+}
+
+impl Capability {
+    /// Every capability, as a root scope normally has.
+    pub const ALL: &'static [Capability] = &[
+        Capability::Core,
+        Capability::Math,
+        Capability::Strings,
+        Capability::Io,
+        Capability::Filesystem,
+        Capability::Process,
+        Capability::Time
+    ];
+
+    /// The capabilities that are safe to give untrusted scripts, which can't
+    /// affect anything outside the interpreter.
+    pub const PURE: &'static [Capability] = &[
+        Capability::Core,
+        Capability::Math,
+        Capability::Strings
+    ];
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::rc::Rc;
+use value::Value;
+use scope::Scope;
+use function::NativeClosure;
+use error::{ErrorKind, Result};
+
+/// Conversion from a Lisp value to a Rust type, failing with a type error if
+/// the value is of the wrong type.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self>;
+}
+
+/// Conversion from a Rust type to a Lisp value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+pub(crate) fn expected<T>(description: &str, value: Value) -> Result<T> {
+    Err(ErrorKind::Type(format!("Expected {}, got: {:?}", description, value)).into())
+}
+
+pub(crate) fn list_items(value: Value) -> Result<Vec<Value>> {
+    if let Value::Vector(values) = value {
+        return Ok(values.borrow().clone());
+    }
+
+    match value.as_list() {
+        Some(list) => Ok(list.into_iter().map(|value| value.deref().clone()).collect()),
+        None => expected("list", value)
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Integer(n) => Ok(n),
+            value => expected("integer", value)
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Integer(self)
+    }
+}
+
+impl FromValue for f64 {
+    /// Any number converts to a float, even if that loses precision.
+    fn from_value(value: Value) -> Result<Self> {
+        match value.as_float() {
+            Some(n) => Ok(n),
+            None => expected("number", value)
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Boolean(b) => Ok(b),
+            value => expected("boolean", value)
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self> {
+        value.as_string()
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Char(c) => Ok(c),
+            value => expected("character", value)
+        }
+    }
+}
+
+impl IntoValue for char {
+    fn into_value(self) -> Value {
+        Value::Char(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self> {
+        list_items(value)?.into_iter().map(T::from_value).collect()
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::list(self.into_iter().map(T::into_value))
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    /// Nil converts to `None`, and anything else to `Some`.
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_value(value).map(Some)
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Nil, T::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    /// Converts from a hash table or an association list of `(key . value)`
+    /// pairs, with string, symbol or keyword keys.
+    fn from_value(value: Value) -> Result<Self> {
+        let entries = match value {
+            Value::HashMap(map) => {
+                map.borrow().iter()
+                    .map(|(key, value)| {
+                        Value::cons(key.to_value(), value.clone())
+                    })
+                    .collect()
+            },
+            value => list_items(value)?
+        };
+
+        entries.into_iter()
+            .map(|entry| match entry {
+                Value::Cons(key, value) => {
+                    let key = match key.deref() {
+                        Value::String(key) => key.clone(),
+                        Value::Symbol(key) | Value::Keyword(key) => key.to_string(),
+                        key => return expected("string, symbol or keyword key", key.clone())
+                    };
+
+                    Ok((key, T::from_value(value.deref().clone())?))
+                },
+                entry => expected("key and value pair", entry)
+            })
+            .collect()
+    }
+}
+
+impl<K: IntoValue + Ord + Hash, T: IntoValue> IntoValue for HashMap<K, T> {
+    /// Converts to an association list of `(key . value)` pairs, sorted by
+    /// key so the result doesn't depend on the map's iteration order.
+    fn into_value(self) -> Value {
+        let mut entries: Vec<_> = self.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Value::list(entries.into_iter().map(|(key, value)| {
+            Value::cons(key.into_value(), value.into_value())
+        }))
+    }
+}
+
+macro_rules! impl_tuple {
+    ($len:expr; $($name:ident),+) => {
+        impl<$($name: FromValue),+> FromValue for ($($name,)+) {
+            /// Converts from a list with exactly one element per field.
+            fn from_value(value: Value) -> Result<Self> {
+                let items = list_items(value)?;
+
+                if items.len() != $len {
+                    return expected(&format!("list of length {}", $len),
+                                    Value::list(items.into_iter()));
+                }
+
+                let mut iter = items.into_iter();
+                Ok(($($name::from_value(iter.next().unwrap())?,)+))
+            }
+        }
+
+        impl<$($name: IntoValue),+> IntoValue for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn into_value(self) -> Value {
+                let ($($name,)+) = self;
+                Value::list(vec![$($name.into_value()),+].into_iter())
+            }
+        }
+    }
+}
+
+impl_tuple!(1; A);
+impl_tuple!(2; A, B);
+impl_tuple!(3; A, B, C);
+impl_tuple!(4; A, B, C, D);
+impl_tuple!(5; A, B, C, D, E);
+
+/// A Rust function that can be called from Lisp, with its arguments and
+/// result converted automatically. `Args` is the tuple of its argument types.
+pub trait NativeFn<Args> {
+    fn into_closure(self, name: &str) -> NativeClosure;
+}
+
+macro_rules! impl_native_fn {
+    ($len:expr; $($name:ident),*) => {
+        impl<Func, Ret, $($name),*> NativeFn<($($name,)*)> for Func
+            where Func: Fn($($name),*) -> Result<Ret> + 'static,
+                  Ret: IntoValue,
+                  $($name: FromValue),*
+        {
+            #[allow(unused_mut, unused_variables)]
+            fn into_closure(self, name: &str) -> NativeClosure {
+                NativeClosure::new(name, move |args: Vec<Value>, _scope: Rc<Scope>| {
+                    if args.len() != $len {
+                        let noun = if $len == 1 { "argument" } else { "arguments" };
+                        return Err(ErrorKind::Arity(format!("Expected {} {}, got {}",
+                                                            $len, noun, args.len())).into());
+                    }
+
+                    let mut iter = args.into_iter();
+                    Ok(self($($name::from_value(iter.next().unwrap())?),*)?.into_value())
+                })
+            }
+        }
+    }
+}
+
+impl_native_fn!(0;);
+impl_native_fn!(1; A);
+impl_native_fn!(2; A, B);
+impl_native_fn!(3; A, B, C);
+impl_native_fn!(4; A, B, C, D);
+impl_native_fn!(5; A, B, C, D, E);
@@ -0,0 +1,192 @@
+use std::fmt::Display;
+use std::ops::Deref;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use num_traits::ToPrimitive;
+use value::Value;
+use error::{Error, ErrorKind, Result};
+use convert::{expected, list_items};
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        ErrorKind::Type(msg.to_string()).into()
+    }
+}
+
+/// Converts a Lisp value to Rust data, accepting the representation that
+/// `to_value` produces.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T> {
+    T::deserialize(Deserializer(value))
+}
+
+struct Deserializer(Value);
+
+impl<'de> IntoDeserializer<'de, Error> for Deserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Nil => visitor.visit_unit(),
+            Value::Boolean(b) => visitor.visit_bool(b),
+            Value::Integer(n) => visitor.visit_i64(n),
+            Value::BigInt(n) => {
+                match n.to_u64() {
+                    Some(n) => visitor.visit_u64(n),
+                    None => expected("integer that fits in 64 bits", Value::BigInt(n))
+                }
+            },
+            value @ Value::Ratio(_) | value @ Value::Float(_) => {
+                visitor.visit_f64(value.as_float().unwrap())
+            },
+            Value::String(s) => visitor.visit_string(s),
+            Value::Char(c) => visitor.visit_char(c),
+            Value::Symbol(s) | Value::Keyword(s) => visitor.visit_str(s.as_str()),
+            value @ Value::Cons(_, _) | value @ Value::Vector(_) => {
+                Deserializer(value).deserialize_seq(visitor)
+            },
+            value @ Value::HashMap(_) => Deserializer(value).deserialize_map(visitor),
+            value => expected("data", value)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Value::Nil => visitor.visit_none(),
+            value => visitor.visit_some(Deserializer(value))
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str,
+                                                   visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut items = de::value::SeqDeserializer::new(list_items(self.0)?.into_iter()
+                                                        .map(Deserializer));
+        let result = visitor.visit_seq(&mut items)?;
+        items.end()?;
+        Ok(result)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize,
+                                                 visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    /// Maps are read from hash tables or association lists of `(key . value)`
+    /// pairs.
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let entries = match self.0 {
+            Value::HashMap(map) => {
+                Value::sorted_entries(&map.borrow()).into_iter()
+                    .map(|(key, value)| Value::cons(key, value))
+                    .collect()
+            },
+            value => list_items(value)?
+        };
+
+        let entries = entries.into_iter()
+            .map(|entry| match entry {
+                Value::Cons(key, value) => {
+                    Ok((Deserializer(key.deref().clone()), Deserializer(value.deref().clone())))
+                },
+                entry => expected("key and value pair", entry)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut entries = de::value::MapDeserializer::new(entries.into_iter());
+        let result = visitor.visit_map(&mut entries)?;
+        entries.end()?;
+        Ok(result)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str,
+                                           _fields: &'static [&'static str],
+                                           visitor: V) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    /// Unit variants are read from a keyword, and others from a list tagged
+    /// with one.
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str,
+                                         _variants: &'static [&'static str],
+                                         visitor: V) -> Result<V::Value> {
+        let (tag, content) = match self.0 {
+            Value::Cons(tag, content) => (tag.deref().clone(), content.deref().clone()),
+            value => (value, Value::Nil)
+        };
+
+        let variant = match tag {
+            Value::Keyword(s) | Value::Symbol(s) => s.to_string(),
+            Value::String(s) => s,
+            tag => return expected("keyword naming an enum variant", tag)
+        };
+
+        visitor.visit_enum(Enum { variant, content })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct identifier ignored_any
+    }
+}
+
+struct Enum {
+    variant: String,
+    content: Value
+}
+
+impl<'de> de::EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Variant;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Variant)> {
+        let variant = seed.deserialize(Deserializer(Value::String(self.variant)))?;
+        Ok((variant, Variant(self.content)))
+    }
+}
+
+// The elements of an enum variant's list that follow its tag.
+struct Variant(Value);
+
+impl<'de> de::VariantAccess<'de> for Variant {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.0 {
+            Value::Nil => Ok(()),
+            value => expected("no values for a unit variant", value)
+        }
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value> {
+        let mut items = list_items(self.0)?;
+
+        if items.len() != 1 {
+            return expected("one value for a newtype variant", Value::list(items.into_iter()));
+        }
+
+        seed.deserialize(Deserializer(items.remove(0)))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(Deserializer(self.0), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str],
+                                       visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(Deserializer(self.0), visitor)
+    }
+}
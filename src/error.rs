@@ -0,0 +1,142 @@
+use std::fmt;
+use std::result;
+use location::Location;
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ErrorKind {
+    /// A symbol was evaluated that isn't bound in any enclosing scope.
+    UnboundSymbol(String),
+    /// A value was of the wrong type for the operation applied to it.
+    Type(String),
+    /// A function, macro or special form was called with the wrong arguments.
+    Arity(String),
+    /// An arithmetic operation overflowed or divided by zero.
+    Arithmetic(String),
+    /// The input could not be read.
+    Parse(String),
+    /// An error raised by a script using `error`.
+    User(String),
+    /// An operation on the outside world, such as reading a file, failed.
+    Io(String),
+    /// A script tried to redefine a binding that has been frozen.
+    Frozen(String),
+    /// Evaluation used more of some resource than its `Limits` allow.
+    LimitExceeded(String),
+    /// Evaluation was stopped through an `InterruptHandle`.
+    Interrupted,
+    /// Not really an error: a script called `exit` with the given status,
+    /// which unwinds evaluation so the embedding application can exit.
+    Exit(i32)
+}
+
+/// The most frames that are shown when an error is displayed, so that deep
+/// recursion doesn't bury the error message.
+const MAX_DISPLAYED_FRAMES: usize = 20;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub name: String,
+    pub location: Option<Location>
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if let Some(ref location) = self.location {
+            write!(f, " at {}", location)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    location: Option<Location>,
+    backtrace: Vec<Frame>
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The innermost expression being evaluated (or read) when the error
+    /// occurred, if it came from source with known locations.
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+
+    /// The calls that were being evaluated when the error occurred, starting
+    /// with the innermost.
+    pub fn backtrace(&self) -> &[Frame] {
+        &self.backtrace
+    }
+
+    /// Attaches a location unless a more specific one is already known.
+    pub fn at(mut self, location: Option<Location>) -> Self {
+        if self.location.is_none() {
+            self.location = location;
+        }
+
+        self
+    }
+
+    /// Records a call the error is propagating out of.
+    pub fn in_frame(mut self, frame: Frame) -> Self {
+        self.backtrace.push(frame);
+        self
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error { kind, location: None, backtrace: Vec::new() }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ErrorKind::*;
+
+        match self {
+            UnboundSymbol(symbol) => write!(f, "Symbol not found: {}", symbol),
+            Type(msg) => write!(f, "{}", msg),
+            Arity(msg) => write!(f, "{}", msg),
+            Arithmetic(msg) => write!(f, "{}", msg),
+            Parse(msg) => write!(f, "Parse error: {}", msg),
+            User(msg) => write!(f, "{}", msg),
+            Io(msg) => write!(f, "{}", msg),
+            Frozen(symbol) => write!(f, "Cannot redefine frozen binding: {}", symbol),
+            LimitExceeded(msg) => write!(f, "Limit exceeded: {}", msg),
+            Interrupted => write!(f, "Interrupted"),
+            Exit(status) => write!(f, "Exited with status {}", status)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref location) = self.location {
+            write!(f, "{}: ", location)?;
+        }
+
+        write!(f, "{}", self.kind)?;
+
+        for frame in self.backtrace.iter().take(MAX_DISPLAYED_FRAMES) {
+            write!(f, "\n    in {}", frame)?;
+        }
+
+        if self.backtrace.len() > MAX_DISPLAYED_FRAMES {
+            write!(f, "\n    ... {} more", self.backtrace.len() - MAX_DISPLAYED_FRAMES)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ::std::error::Error for Error {}
@@ -1,20 +1,38 @@
+use std::collections::HashMap;
 use std::fmt;
+use num_bigint::BigInt;
+use num_rational::BigRational;
 use value::Value;
+use hash_key::HashKey;
 use itertools::Itertools;
+use error::{ErrorKind, Result};
+use location::{self, Source};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     Float(f64),
     Integer(i64),
+    BigInt(BigInt),
+    Ratio(BigRational),
     String(String),
+    Char(char),
     Symbol(String),
-    Sexpr(Vec<Expr>),
-    TemplateExpr(Box<Expr>),
-    TemplateListExpr(Box<Expr>)
+    /// A keyword, named without its colon.
+    Keyword(String),
+    /// A parenthesized list, along with the byte offset of its opening paren.
+    Sexpr(Vec<Expr>, usize),
+    /// A bracketed vector literal.
+    Vector(Vec<Expr>),
+    /// A braced hash table literal of alternating keys and values.
+    Map(Vec<Expr>),
+    /// A `,` expression inside a quasiquoted template.
+    Unquote(Box<Expr>),
+    /// A `,@` expression whose list is spliced into a quasiquoted template.
+    UnquoteSplicing(Box<Expr>)
 }
 
 impl Expr {
-    pub fn as_symbol(self) -> Option<String> {
+    pub fn into_symbol(self) -> Option<String> {
         if let Expr::Symbol(sym) = self {
             Some(sym)
         } else {
@@ -22,16 +40,16 @@ impl Expr {
         }
     }
 
-    pub fn as_sexpr(self) -> Option<Vec<Expr>> {
-        if let Expr::Sexpr(expressions) = self {
+    pub fn into_sexpr(self) -> Option<Vec<Expr>> {
+        if let Expr::Sexpr(expressions, _) = self {
             Some(expressions)
         } else {
             None
         }
     }
 
-    pub fn as_pair(self) -> Option<(Expr, Expr)> {
-        if let Expr::Sexpr(expressions) = self {
+    pub fn into_pair(self) -> Option<(Expr, Expr)> {
+        if let Expr::Sexpr(expressions, _) = self {
             if expressions.len() == 2 {
                 let mut iter = expressions.into_iter();
                 return Some((iter.next().unwrap(),
@@ -42,9 +60,9 @@ impl Expr {
         None
     }
 
-    pub fn as_symbol_value_pair(self) -> Option<(String, Expr)> {
-        if let Some((left, right)) = self.as_pair() {
-            if let Some(symbol) = left.as_symbol() {
+    pub fn into_symbol_value_pair(self) -> Option<(String, Expr)> {
+        if let Some((left, right)) = self.into_pair() {
+            if let Some(symbol) = left.into_symbol() {
                 return Some((symbol, right));
             }
         }
@@ -52,7 +70,7 @@ impl Expr {
         None
     }
 
-    pub fn as_string(self) -> Option<String> {
+    pub fn into_string(self) -> Option<String> {
         if let Expr::String(s) = self {
             Some(s)
         } else {
@@ -60,55 +78,119 @@ impl Expr {
         }
     }
 
-    pub fn into_value(self) -> Value {
+    pub fn into_value(self, source: &Source) -> Result<Value> {
         match self {
-            Expr::Integer(i) => Value::Integer(i),
-            Expr::Float(f) => Value::Float(f),
-            Expr::String(s) => Value::String(s),
-            Expr::Symbol(sym) => Value::Symbol(sym),
-            Expr::Sexpr(exprs) => {
-                Value::list(exprs.into_iter().map(|e| e.into_value()))
+            Expr::Integer(i) => Ok(Value::Integer(i)),
+            Expr::BigInt(n) => Ok(Value::from(n)),
+            Expr::Ratio(n) => Ok(Value::from(n)),
+            Expr::Float(f) => Ok(Value::Float(f)),
+            Expr::String(s) => Ok(Value::String(s)),
+            Expr::Char(c) => Ok(Value::Char(c)),
+            Expr::Symbol(sym) => Ok(Value::symbol(&sym)),
+            Expr::Keyword(name) => Ok(Value::keyword(&name)),
+            Expr::Sexpr(exprs, offset) => {
+                let values = exprs.into_iter()
+                    .map(|e| e.into_value(source))
+                    .collect::<Result<Vec<_>>>()?;
+                let list = Value::list(values.into_iter());
+
+                if let Value::Cons(ref car, _) = list {
+                    location::record(car, source.locate(offset));
+                }
+
+                Ok(list)
+            },
+            Expr::Vector(exprs) => {
+                let values = exprs.into_iter()
+                    .map(|e| e.into_value(source))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Value::vector(values))
+            },
+            Expr::Map(exprs) => {
+                if exprs.len() % 2 != 0 {
+                    return Err(ErrorKind::Parse("Expected a value for every key in hash table"
+                                                .to_string()).into());
+                }
+
+                let mut map = HashMap::new();
+
+                for (key, value) in exprs.into_iter().tuples() {
+                    let key = key.into_value(source)?;
+                    let key = HashKey::from_value(key.clone())
+                        .map_err(|_| ErrorKind::Parse(format!("Expected a string, character, \
+                                                               integer, symbol, keyword or \
+                                                               boolean key, got: {:?}", key)))?;
+
+                    map.insert(key, value.into_value(source)?);
+                }
+
+                Ok(Value::hash_map(map))
+            },
+            Expr::Unquote(_) => {
+                Err(ErrorKind::Parse("Comma not inside backquote".to_string()).into())
             },
-            Expr::TemplateExpr(_) | Expr::TemplateListExpr(_) => {
-                panic!("Comma not inside backquote");
+            Expr::UnquoteSplicing(_) => {
+                Err(ErrorKind::Parse("Comma-at not inside a backquoted list".to_string())
+                    .into())
             }
         }
     }
 
-    pub fn progn(expressions: Vec<Expr>) -> Expr {
+    pub fn progn(expressions: Vec<Expr>, offset: usize) -> Expr {
         let mut sexpr = vec![Expr::Symbol("progn".to_string())];
         sexpr.extend(expressions);
-        Expr::Sexpr(sexpr)
+        Expr::Sexpr(sexpr, offset)
     }
 
-    pub fn quote(expr: Expr) -> Expr {
-        Expr::Sexpr(vec![Expr::Symbol("quote".to_string()), expr])
+    pub fn quote(expr: Expr, offset: usize) -> Expr {
+        Expr::Sexpr(vec![Expr::Symbol("quote".to_string()), expr], offset)
     }
 
-    pub fn template(expr: Expr) -> Expr {
+    pub fn template(expr: Expr, offset: usize) -> Expr {
         match expr {
-            Expr::Symbol(s) => Expr::quote(Expr::Symbol(s)),
-            Expr::Sexpr(children) => {
+            Expr::Symbol(s) => Expr::quote(Expr::Symbol(s), offset),
+            Expr::Sexpr(children, offset) => {
                 let mut sexpr = vec![Expr::symbol("append")];
 
                 for child in children.into_iter() {
-                    let child = if let Expr::TemplateListExpr(e) = child {
+                    let child = if let Expr::UnquoteSplicing(e) = child {
                         e.as_ref().clone()
                     } else {
                         Expr::Sexpr(vec![Expr::symbol("list"),
-                                         Expr::template(child)])
+                                         Expr::template(child, offset)],
+                                    offset)
                     };
                     sexpr.push(child);
                 }
 
                 sexpr.push(Expr::symbol("nil"));
 
-                Expr::Sexpr(sexpr)
+                Expr::Sexpr(sexpr, offset)
             },
-            Expr::TemplateExpr(e) => e.as_ref().clone(),
-            Expr::TemplateListExpr(_) => {
-                panic!("Cannot expand list at top level of template")
+            Expr::Vector(children) => {
+                Expr::Sexpr(vec![Expr::symbol("list->vector"),
+                                 Expr::template(Expr::Sexpr(children, offset), offset)],
+                            offset)
             },
+            // Keys can't be lists, so they're left as they are, other than
+            // removing any comma. Like those of any table literal, they're
+            // evaluated along with the templated values.
+            Expr::Map(children) => {
+                Expr::Map(children.into_iter()
+                          .tuples()
+                          .flat_map(|(key, value)| {
+                              let key = match key {
+                                  Expr::Unquote(e) => *e,
+                                  key => key
+                              };
+                              vec![key, Expr::template(value, offset)]
+                          })
+                          .collect())
+            },
+            Expr::Unquote(e) => e.as_ref().clone(),
+            // Left in place so that `into_value` reports it, since there is
+            // no list at the top level of the template to splice into.
+            Expr::UnquoteSplicing(_) => expr,
             _ => expr
         }
     }
@@ -124,20 +206,30 @@ impl fmt::Display for Expr {
 
         match self {
             Integer(n) => write!(f, "{}", n),
+            Expr::BigInt(n) => write!(f, "{}", n),
+            Expr::Ratio(n) => write!(f, "{}", n),
             Float(n) => write!(f, "{}", n),
             String(s) => write!(f, "{:?}", s),
+            Char(c) => write!(f, "{:?}", Value::Char(*c)),
             Symbol(s) => write!(f, "{}", s),
-            Sexpr(expressions) => {
+            Keyword(name) => write!(f, ":{}", name),
+            Sexpr(expressions, _) => {
                 if expressions.len() == 2 && expressions[0] == Expr::Symbol("quote".to_string()) {
 
                     write!(f, "'{}", expressions[1])
                 } else {
-                    write!(f, "({})", expressions.into_iter()
+                    write!(f, "({})", expressions.iter()
                            .map(|e| format!("{}", e)).join(" "))
                 }
             },
-            Expr::TemplateExpr(e) => write!(f, ",{}", e),
-            Expr::TemplateListExpr(e) => write!(f, ",@{}", e)
+            Vector(expressions) => {
+                write!(f, "[{}]", expressions.iter().map(|e| format!("{}", e)).join(" "))
+            },
+            Map(expressions) => {
+                write!(f, "{{{}}}", expressions.iter().map(|e| format!("{}", e)).join(" "))
+            },
+            Expr::Unquote(e) => write!(f, ",{}", e),
+            Expr::UnquoteSplicing(e) => write!(f, ",@{}", e)
         }
     }
 }
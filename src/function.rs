@@ -1,32 +1,44 @@
 use std::rc::Rc;
+use std::ptr;
 use value::Value;
 use scope::Scope;
 use params::Params;
+use error::Result;
 
 
 #[derive(PartialEq)]
 pub struct Function {
-    pub name: String,
+    pub name: Option<String>,
     params: Params,
-    expr: Value,
+    expr: Rc<Value>,
     parent_scope: Rc<Scope>
 }
 
 impl Function {
-    pub fn define(name: String, params: &Value, body: Rc<Value>,
-                  parent_scope: Rc<Scope>) -> Self {
-        Function {
+    pub fn define(name: Option<String>, params: &Value, body: Rc<Value>,
+                  parent_scope: Rc<Scope>) -> Result<Self> {
+        Ok(Function {
             name,
-            params: Params::parse(params),
-            expr: Value::progn(body),
+            params: Params::parse(params)?,
+            expr: Rc::new(Value::progn(body)),
             parent_scope
-        }
+        })
     }
 
-    pub fn call(&self, args: Vec<Value>) -> Value {
+    /// Binds `args` to the function's parameters, returning the scope its
+    /// body is evaluated in.
+    pub fn bind(&self, args: Vec<Value>) -> Result<Rc<Scope>> {
         let scope = self.parent_scope.clone().push();
-        self.params.apply(&scope, args);
-        self.expr.clone().eval(&scope)
+        self.params.apply(&scope, args)?;
+        Ok(scope)
+    }
+
+    pub fn body(&self) -> Rc<Value> {
+        self.expr.clone()
+    }
+
+    pub fn call(&self, args: Vec<Value>) -> Result<Value> {
+        self.expr.eval(&self.bind(args)?)
     }
 }
 
@@ -40,18 +52,49 @@ pub struct Macro {
 
 impl Macro {
     pub fn define(name: String, params: &Value, body: Rc<Value>,
-                  parent_scope: Rc<Scope>) -> Self {
-        Macro {
+                  parent_scope: Rc<Scope>) -> Result<Self> {
+        Ok(Macro {
             name,
-            params: Params::parse(params),
+            params: Params::parse(params)?,
             expr: Value::progn(body),
             parent_scope
-        }
+        })
     }
 
-    pub fn call(&self, args: Vec<Value>) -> Value {
+    pub fn call(&self, args: Vec<Value>) -> Result<Value> {
         let scope = self.parent_scope.clone().push();
-        self.params.apply(&scope, args);
+        self.params.apply(&scope, args)?;
         self.expr.clone().eval(&scope)
     }
 }
+
+type ClosureFn = dyn Fn(Vec<Value>, Rc<Scope>) -> Result<Value>;
+
+/// A function implemented in Rust that, unlike `Value::NativeFunction`, can
+/// capture state such as a handle owned by the embedding application.
+pub struct NativeClosure {
+    pub name: String,
+    func: Box<ClosureFn>
+}
+
+impl NativeClosure {
+    pub fn new<F>(name: &str, func: F) -> Self
+        where F: Fn(Vec<Value>, Rc<Scope>) -> Result<Value> + 'static
+    {
+        NativeClosure {
+            name: name.to_string(),
+            func: Box::new(func)
+        }
+    }
+
+    pub fn call(&self, args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+        (self.func)(args, scope)
+    }
+}
+
+// Closures can't be compared, so they're only equal to themselves.
+impl PartialEq for NativeClosure {
+    fn eq(&self, other: &NativeClosure) -> bool {
+        ptr::eq(self, other)
+    }
+}
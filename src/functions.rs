@@ -1,55 +1,1192 @@
-use std::collections::HashMap;
-use value::{Value, Reduce};
+use std::collections::{self, HashMap};
+use std::cmp::Ordering;
+use std::env;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use itertools::Itertools;
+use value::Value;
+use hash_key::HashKey;
+use symbol::Symbol;
+use scope::Scope;
+use expr::Expr;
+use parser;
+use error::{Error, ErrorKind, Result};
+use capability::Capability;
 
-fn println(args: Vec<Value>) -> Value {
-    use runtime_fmt::{FormatBuf, Param, _print};
+/// Formats `args` like Rust's `format!`, with `{}` for display and `{:?}` for
+/// debug output. Either may name an argument by index, as in `{1:?}`.
+fn format_values(format: &str, args: &[Value]) -> Result<String> {
+    let invalid = |message: String| -> Error {
+        ErrorKind::Type(format!("Invalid format string or arguments: {}", message)).into()
+    };
 
-    let mut iter = args.iter();
-    let format_str = iter.next()
-        .expect("Expected at least one argument for the format string")
-        .clone()
-        .as_string();
-    let values: Vec<Param> = iter.map(|v| Param::normal(v)).collect();
+    let mut formatted = String::new();
+    let mut chars = format.chars().peekable();
+    let mut next_arg = 0;
 
-    FormatBuf::new(&format_str, &values)
-        .map(|mut x| x.newln().with(_print))
-        .expect("Invalid format string or arguments");
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                formatted.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                formatted.push('}');
+            },
+            '{' => {
+                let mut spec = String::new();
 
-    Value::Nil
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => spec.push(c),
+                        None => return Err(invalid(format!("unclosed {{ in {:?}", format)))
+                    }
+                }
+
+                let (index, style) = match spec.find(':') {
+                    Some(colon) => (&spec[..colon], &spec[colon + 1..]),
+                    None => (spec.as_str(), "")
+                };
+                let index = if index.is_empty() {
+                    next_arg += 1;
+                    next_arg - 1
+                } else {
+                    index.parse()
+                        .map_err(|_| invalid(format!("bad argument index {:?}", index)))?
+                };
+                let arg = args.get(index)
+                    .ok_or_else(|| invalid(format!("no argument {}", index)))?;
+
+                match style {
+                    "" => formatted.push_str(&arg.to_string()),
+                    "?" => formatted.push_str(&format!("{:?}", arg)),
+                    _ => return Err(invalid(format!("unsupported format {:?}", style)))
+                }
+            },
+            '}' => return Err(invalid(format!("unmatched }} in {:?}", format))),
+            c => formatted.push(c)
+        }
+    }
+
+    Ok(formatted)
+}
+
+fn println(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (format, args) = args.split_first()
+        .ok_or_else(|| ErrorKind::Arity("Expected at least one argument for the format string"
+                                    .to_string()))?;
+
+    println!("{}", format_values(&format.clone().as_string()?, args)?);
+    Ok(Value::Nil)
+}
+
+fn error(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let mut iter = args.into_iter();
+    let message = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected an error message".to_string()))?
+        .as_string()?;
+
+    if let Some(arg) = iter.next() {
+        return Err(ErrorKind::Arity(format!("Unexpected additional argument: {:?}", arg)).into());
+    }
+
+    Err(ErrorKind::User(message).into())
+}
+
+fn exit(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    if args.len() > 1 {
+        return Err(ErrorKind::Arity("Expected at most one argument".to_string()).into());
+    }
+
+    let status = match args.into_iter().next() {
+        Some(Value::Integer(n)) if (0..=255).contains(&n) => n as i32,
+        Some(status) => {
+            return Err(ErrorKind::Type(format!("Expected integer exit status from 0 to 255, \
+                                                got: {:?}", status)).into())
+        },
+        None => 0
+    };
+
+    Err(ErrorKind::Exit(status).into())
+}
+
+fn no_args(args: Vec<Value>) -> Result<()> {
+    match args.into_iter().next() {
+        Some(arg) => Err(ErrorKind::Arity(format!("Unexpected argument: {:?}", arg)).into()),
+        None => Ok(())
+    }
+}
+
+/// Makes a string, counting its bytes against the scope's limits.
+fn new_string(s: String, scope: &Scope) -> Result<Value> {
+    scope.budget().allocate_string_bytes(s.len())?;
+    Ok(Value::String(s))
+}
+
+fn string_length(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(Value::Integer(one_arg(args)?.as_string()?.chars().count() as i64))
+}
+
+fn string_append(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let strings = args.into_iter()
+        .map(Value::as_string)
+        .collect::<Result<Vec<_>>>()?;
+
+    new_string(strings.concat(), &scope)
+}
+
+/// The byte offset in a string of a character index, which may be the index
+/// just past the end.
+fn char_offset(s: &str, index: Value) -> Result<usize> {
+    let len = s.chars().count();
+
+    match index {
+        Value::Integer(n) if n >= 0 && n as u64 <= len as u64 => {
+            Ok(s.char_indices().map(|(i, _)| i).nth(n as usize).unwrap_or(s.len()))
+        },
+        Value::Integer(n) => {
+            Err(ErrorKind::Type(format!("Index {} out of range for string of length {}", n, len))
+                .into())
+        },
+        index => Err(ErrorKind::Type(format!("Expected an integer index, got: {:?}", index)).into())
+    }
+}
+
+/// The characters from a start index up to an optional end index.
+fn substring(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(ErrorKind::Arity("Expected a string, a start index and an optional end index"
+                                    .to_string()).into());
+    }
+
+    let mut iter = args.into_iter();
+    let s = iter.next().unwrap().as_string()?;
+    let start = char_offset(&s, iter.next().unwrap())?;
+    let end = match iter.next() {
+        Some(end) => char_offset(&s, end)?,
+        None => s.len()
+    };
+
+    if start > end {
+        return Err(ErrorKind::Type("Start index is after end index".to_string()).into());
+    }
+
+    new_string(s[start..end].to_string(), &scope)
+}
+
+fn non_empty_string(value: Value) -> Result<String> {
+    let s = value.as_string()?;
+
+    if s.is_empty() {
+        return Err(ErrorKind::Type("Expected a non-empty string".to_string()).into());
+    }
+
+    Ok(s)
+}
+
+/// Splits a string at each occurrence of a separator, or at whitespace if
+/// there isn't one.
+fn string_split(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(ErrorKind::Arity("Expected a string and an optional separator".to_string())
+                   .into());
+    }
+
+    let mut iter = args.into_iter();
+    let s = iter.next().unwrap().as_string()?;
+    let parts: Vec<_> = match iter.next() {
+        Some(separator) => {
+            let separator = non_empty_string(separator)?;
+            s.split(separator.as_str()).map(|part| Value::String(part.to_string())).collect()
+        },
+        None => s.split_whitespace().map(|part| Value::String(part.to_string())).collect()
+    };
+
+    scope.budget().allocate_string_bytes(s.len())?;
+    new_list(parts, &scope)
+}
+
+/// Joins a list of strings, with an optional separator between them.
+fn string_join(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(ErrorKind::Arity("Expected a list of strings and an optional separator"
+                                    .to_string()).into());
+    }
+
+    let mut iter = args.into_iter();
+    let strings = proper_list(&iter.next().unwrap())?.into_iter()
+        .map(|value| value.deref().clone().as_string())
+        .collect::<Result<Vec<_>>>()?;
+    let separator = match iter.next() {
+        Some(separator) => separator.as_string()?,
+        None => String::new()
+    };
+
+    new_string(strings.join(&separator), &scope)
+}
+
+fn string_trim(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_string(one_arg(args)?.as_string()?.trim().to_string(), &scope)
+}
+
+fn string_upcase(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_string(one_arg(args)?.as_string()?.to_uppercase(), &scope)
+}
+
+fn string_downcase(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_string(one_arg(args)?.as_string()?.to_lowercase(), &scope)
+}
+
+/// The character index of the first occurrence of a character or string, or
+/// nil if there isn't one.
+fn string_index(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (s, pattern) = two_args(args)?;
+    let s = s.as_string()?;
+    let offset = match pattern {
+        Value::Char(c) => s.find(c),
+        pattern => s.find(pattern.as_string()?.as_str())
+    };
+
+    Ok(offset.map_or(Value::Nil, |offset| Value::Integer(s[..offset].chars().count() as i64)))
+}
+
+/// Replaces every occurrence of one string with another.
+fn string_replace(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.len() != 3 {
+        return Err(ErrorKind::Arity("Expected a string, a string to replace and its replacement"
+                                    .to_string()).into());
+    }
+
+    let mut iter = args.into_iter();
+    let s = iter.next().unwrap().as_string()?;
+    let from = non_empty_string(iter.next().unwrap())?;
+    let to = iter.next().unwrap().as_string()?;
+
+    new_string(s.replace(from.as_str(), &to), &scope)
+}
+
+fn string_to_list(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_list(one_arg(args)?.as_string()?.chars().map(Value::Char).collect(), &scope)
+}
+
+/// Reads a number as the reader would, giving nil if the whole string isn't
+/// one.
+fn string_to_number(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let s = one_arg(args)?.as_string()?;
+
+    Ok(match parser::parse_number(&s) {
+        Some(Expr::Integer(n)) => Value::Integer(n),
+        Some(Expr::BigInt(n)) => Value::from(n),
+        Some(Expr::Ratio(n)) => Value::from(n),
+        Some(Expr::Float(n)) => Value::Float(n),
+        _ => Value::Nil
+    })
+}
+
+fn number_to_string(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_string(format!("{:?}", one_arg(args)?.expect_number()?), &scope)
+}
+
+/// Interns a symbol, charging a new name against the string limit since it's
+/// never freed.
+fn string_to_symbol(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let name = one_arg(args)?.as_string()?;
+
+    if !Symbol::is_interned(&name) {
+        scope.budget().allocate_string_bytes(name.len())?;
+    }
+
+    Ok(Value::symbol(&name))
+}
+
+/// The name of a symbol or keyword, without a keyword's colon.
+fn symbol_to_string(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    match one_arg(args)? {
+        Value::Symbol(s) | Value::Keyword(s) => new_string(s.as_str().to_string(), &scope),
+        value => {
+            Err(ErrorKind::Type(format!("Expected symbol or keyword, got: {:?}", value)).into())
+        }
+    }
+}
+
+/// Reads a line from standard input without its line break, returning nil
+/// at the end of the input.
+fn read_line(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    no_args(args)?;
+
+    let mut line = String::new();
+    let len = io::stdin().read_line(&mut line)
+        .map_err(|e| ErrorKind::Io(format!("Unable to read from stdin: {}", e)))?;
+
+    if len == 0 {
+        return Ok(Value::Nil);
+    }
+
+    let trimmed_len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed_len);
+    new_string(line, &scope)
+}
+
+fn read_file(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let path = one_arg(args)?.as_string()?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| ErrorKind::Io(format!("Unable to read {}: {}", path, e)))?;
+
+    new_string(contents, &scope)
+}
+
+fn write_file(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (path, contents) = two_args(args)?;
+    let path = path.as_string()?;
+
+    fs::write(&path, contents.as_string()?)
+        .map_err(|e| ErrorKind::Io(format!("Unable to write {}: {}", path, e)))?;
+
+    Ok(Value::Nil)
+}
+
+fn file_exists(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(Value::Boolean(Path::new(&one_arg(args)?.as_string()?).exists()))
+}
+
+/// The value of an environment variable, or nil if it isn't set.
+fn getenv(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    match env::var(one_arg(args)?.as_string()?) {
+        Ok(value) => new_string(value, &scope),
+        Err(_) => Ok(Value::Nil)
+    }
+}
+
+/// The number of seconds since the Unix epoch.
+fn current_time(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    no_args(args)?;
+
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map_err(|e| ErrorKind::Io(format!("System clock is before the Unix epoch: {}", e)))?;
+
+    Ok(Value::Float(elapsed.as_secs_f64()))
+}
+
+fn sleep(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let seconds = one_arg(args)?;
+    let duration = seconds.as_float()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| {
+            ErrorKind::Type(format!("Expected a non-negative number of seconds, got: {:?}",
+                                    seconds))
+        })?;
+
+    scope.budget().sleep(duration)?;
+    Ok(Value::Nil)
+}
+
+fn equal(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    if args.len() != 2 {
+        return Err(ErrorKind::Arity("Expected two arguments".to_string()).into());
+    }
+
+    Ok(Value::Boolean(args[0] == args[1]))
+}
+
+fn one_arg(args: Vec<Value>) -> Result<Value> {
+    if args.len() != 1 {
+        return Err(ErrorKind::Arity("Expected one argument".to_string()).into());
+    }
+
+    Ok(args.into_iter().next().unwrap())
 }
 
-fn equal(args: Vec<Value>) -> Value {
+fn two_args(args: Vec<Value>) -> Result<(Value, Value)> {
     if args.len() != 2 {
-        panic!("Expected two arguments");
+        return Err(ErrorKind::Arity("Expected two arguments".to_string()).into());
     }
 
-    Value::Boolean(args[0] == args[1])
+    let mut iter = args.into_iter();
+    Ok((iter.next().unwrap(), iter.next().unwrap()))
 }
 
-fn plus(args: Vec<Value>) -> Value {
+fn plus(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let mut iter = args.into_iter();
+    let first = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected at least one argument".to_string()))?;
+
+    // Adding checks the types of any operands, but a lone one isn't added
+    iter.try_fold(first, |sum, value| sum + value)?.expect_number()
+}
+
+fn minus(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let mut iter = args.into_iter().peekable();
+    let first = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected at least one argument".to_string()))?;
+
+    if iter.peek().is_none() {
+        Value::Integer(0) - first
+    } else {
+        iter.try_fold(first, |difference, value| difference - value)
+    }
+}
+
+fn times(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
     args.into_iter()
-        .reduce(|a, b| a + b)
-        .expect("Expected at least two arguments")
+        .try_fold(Value::Integer(1), |product, value| product * value)
 }
 
-fn list(args: Vec<Value>) -> Value {
-    Value::list(args.into_iter())
+fn divide(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let mut iter = args.into_iter().peekable();
+    let first = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected at least one argument".to_string()))?;
+
+    if iter.peek().is_none() {
+        Value::Integer(1) / first
+    } else {
+        iter.try_fold(first, |quotient, value| quotient / value)
+    }
 }
 
-fn append(args: Vec<Value>) -> Value {
-    Value::list_rc(args.into_iter()
-                   .flat_map(|value| value.as_list().expect("Not a proper list")))
+fn modulo(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (a, b) = two_args(args)?;
+    a.modulo(b)
 }
 
-pub fn register(scope: &mut HashMap<String, Value>) {
-    scope.insert("println".to_string(),
-                 Value::NativeFunction("println".to_string(), println));
+fn remainder(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (a, b) = two_args(args)?;
+    a % b
+}
+
+fn abs(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    one_arg(args)?.abs()
+}
+
+fn extremum(args: Vec<Value>, keep: Ordering) -> Result<Value> {
+    let mut iter = args.into_iter();
+    let first = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected at least one argument".to_string()))?
+        .expect_number()?;
+
+    iter.try_fold(first, |best, value| {
+        if value.compare(&best)? == keep {
+            Ok(value)
+        } else {
+            Ok(best)
+        }
+    })
+}
+
+fn min(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    extremum(args, Ordering::Less)
+}
+
+fn max(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    extremum(args, Ordering::Greater)
+}
+
+fn floor(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    one_arg(args)?.floor()
+}
+
+fn ceil(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    one_arg(args)?.ceil()
+}
+
+fn round(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    one_arg(args)?.round()
+}
+
+fn sqrt(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    one_arg(args)?.sqrt()
+}
+
+fn expt(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (base, power) = two_args(args)?;
+    base.expt(power)
+}
+
+/// Checks that each argument is ordered with respect to the next.
+fn compare(args: Vec<Value>, ordered: fn(Ordering) -> bool) -> Result<Value> {
+    if args.is_empty() {
+        return Err(ErrorKind::Arity("Expected at least one argument".to_string()).into());
+    }
+
+    for arg in &args {
+        arg.clone().expect_number()?;
+    }
+
+    let mut result = true;
+
+    for pair in args.windows(2) {
+        result &= ordered(pair[0].compare(&pair[1])?);
+    }
+
+    Ok(Value::Boolean(result))
+}
+
+fn less(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    compare(args, |ordering| ordering == Ordering::Less)
+}
+
+fn greater(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    compare(args, |ordering| ordering == Ordering::Greater)
+}
+
+fn less_or_equal(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    compare(args, |ordering| ordering != Ordering::Greater)
+}
+
+fn greater_or_equal(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    compare(args, |ordering| ordering != Ordering::Less)
+}
+
+fn list(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_list(args, &scope)
+}
+
+/// Builds a list, counting its cells against the scope's limits.
+fn new_list(values: Vec<Value>, scope: &Scope) -> Result<Value> {
+    scope.budget().allocate_cons_cells(values.len())?;
+    Ok(Value::list(values.into_iter()))
+}
+
+fn proper_list(value: &Value) -> Result<Vec<Rc<Value>>> {
+    value.as_list()
+        .ok_or_else(|| ErrorKind::Type(format!("Not a proper list: {:?}", value)).into())
+}
+
+fn append(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let lists = args.iter()
+        .map(proper_list)
+        .collect::<Result<Vec<_>>>()?;
+
+    scope.budget().allocate_cons_cells(lists.iter().map(|list| list.len()).sum())?;
+    Ok(Value::list_rc(lists.into_iter().flat_map(Vec::into_iter)))
+}
+
+fn cons(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let (car, cdr) = two_args(args)?;
+    scope.budget().allocate_cons_cells(1)?;
+    Ok(Value::cons(car, cdr))
+}
+
+/// Splits a list into its first element and the rest, which are both nil
+/// for the empty list.
+fn split_list(value: Value) -> Result<(Value, Value)> {
+    match value {
+        Value::Cons(car, cdr) => Ok((car.deref().clone(), cdr.deref().clone())),
+        Value::Nil => Ok((Value::Nil, Value::Nil)),
+        value => Err(ErrorKind::Type(format!("Expected a list, got: {:?}", value)).into())
+    }
+}
+
+fn car(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(split_list(one_arg(args)?)?.0)
+}
+
+fn cdr(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(split_list(one_arg(args)?)?.1)
+}
+
+fn nth(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (index, list) = two_args(args)?;
+    let index = match index {
+        Value::Integer(n) if n >= 0 => n,
+        index => {
+            return Err(ErrorKind::Type(format!("Expected a non-negative integer index, got: {:?}",
+                                               index)).into())
+        }
+    };
+
+    let list = proper_list(&list)?;
+    Ok(list.get(index as usize).map_or(Value::Nil, |value| value.deref().clone()))
+}
+
+fn length(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(Value::Integer(proper_list(&one_arg(args)?)?.len() as i64))
+}
+
+fn reverse(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let list = proper_list(&one_arg(args)?)?;
+    scope.budget().allocate_cons_cells(list.len())?;
+    Ok(Value::list_rc(list.into_iter().rev()))
+}
+
+fn last(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(proper_list(&one_arg(args)?)?.pop().map_or(Value::Nil, |value| value.deref().clone()))
+}
+
+fn is_null(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(Value::Boolean(one_arg(args)? == Value::Nil))
+}
+
+fn is_list(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(Value::Boolean(one_arg(args)?.as_list().is_some()))
+}
+
+fn is_pair(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    match one_arg(args)? {
+        Value::Cons(_, _) => Ok(Value::Boolean(true)),
+        _ => Ok(Value::Boolean(false))
+    }
+}
+
+fn funcall(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let mut iter = args.into_iter();
+    let func = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected a function to call".to_string()))?;
+
+    func.invoke(iter.collect(), &scope)
+}
+
+/// Calls a function with the given arguments, the last of which is a list of
+/// further arguments.
+fn apply(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.len() < 2 {
+        return Err(ErrorKind::Arity("Expected a function and a list of arguments".to_string())
+                   .into());
+    }
+
+    let mut args = args;
+    let spread = proper_list(&args.pop().unwrap())?;
+    let func = args.remove(0);
+    args.extend(spread.into_iter().map(|value| value.deref().clone()));
+
+    func.invoke(args, &scope)
+}
+
+fn call_predicate(func: &Value, args: Vec<Value>, scope: &Rc<Scope>) -> Result<bool> {
+    match func.invoke(args, scope)? {
+        Value::Boolean(result) => Ok(result),
+        value => {
+            Err(ErrorKind::Type(format!("Expected predicate to return a boolean, got: {:?}",
+                                        value)).into())
+        }
+    }
+}
+
+/// Takes the function and list that most higher-order functions expect.
+fn function_and_list(args: Vec<Value>) -> Result<(Value, Vec<Value>)> {
+    let (func, list) = two_args(args)?;
+    let list = proper_list(&list)?.into_iter().map(|value| value.deref().clone()).collect();
+    Ok((func, list))
+}
+
+/// Calls a function with successive elements of one or more lists, stopping
+/// at the end of the shortest.
+fn map(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.len() < 2 {
+        return Err(ErrorKind::Arity("Expected a function and at least one list".to_string())
+                   .into());
+    }
+
+    let mut iter = args.into_iter();
+    let func = iter.next().unwrap();
+    let lists = iter.map(|list| proper_list(&list)).collect::<Result<Vec<_>>>()?;
+    let len = lists.iter().map(|list| list.len()).min().unwrap();
+
+    let results = (0..len)
+        .map(|i| func.invoke(lists.iter().map(|list| list[i].deref().clone()).collect(), &scope))
+        .collect::<Result<Vec<_>>>()?;
+
+    new_list(results, &scope)
+}
+
+fn filter(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let (func, list) = function_and_list(args)?;
+    let mut results = Vec::new();
+
+    for value in list {
+        if call_predicate(&func, vec![value.clone()], &scope)? {
+            results.push(value);
+        }
+    }
+
+    new_list(results, &scope)
+}
+
+/// Combines the elements of a list from the left, starting with the first.
+fn reduce(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let (func, list) = function_and_list(args)?;
+    let mut iter = list.into_iter();
+    let first = iter.next()
+        .ok_or_else(|| ErrorKind::Type("Unable to reduce an empty list".to_string()))?;
+
+    iter.try_fold(first, |result, value| func.invoke(vec![result, value], &scope))
+}
+
+/// Combines the elements of a list from the left, starting with an initial
+/// value.
+fn fold(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.len() != 3 {
+        return Err(ErrorKind::Arity("Expected a function, an initial value and a list"
+                                    .to_string()).into());
+    }
+
+    let mut iter = args.into_iter();
+    let func = iter.next().unwrap();
+    let initial = iter.next().unwrap();
+    let list = proper_list(&iter.next().unwrap())?;
+
+    list.into_iter()
+        .try_fold(initial, |result, value| func.invoke(vec![result, value.deref().clone()], &scope))
+}
+
+// A stable merge sort, since the comparator can fail and isn't guaranteed to
+// be a consistent ordering.
+fn merge_sort(mut values: Vec<Value>,
+              less: &mut dyn FnMut(&Value, &Value) -> Result<bool>) -> Result<Vec<Value>> {
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+
+    let right = values.split_off(values.len() / 2);
+    let mut left = merge_sort(values, less)?.into_iter().peekable();
+    let mut right = merge_sort(right, less)?.into_iter().peekable();
+    let mut merged = Vec::new();
+
+    loop {
+        let take_right = match (left.peek(), right.peek()) {
+            (Some(l), Some(r)) => less(r, l)?,
+            (Some(_), None) => false,
+            (None, Some(_)) => true,
+            (None, None) => break
+        };
+
+        merged.push(if take_right { right.next() } else { left.next() }.unwrap());
+    }
+
+    Ok(merged)
+}
+
+/// Sorts a list of numbers, or of any values given a predicate that tests
+/// whether one value should come before another.
+fn sort(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let mut iter = args.into_iter();
+    let list = proper_list(&iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected a list to sort".to_string()))?)?;
+    let comparator = iter.next();
+
+    if let Some(arg) = iter.next() {
+        return Err(ErrorKind::Arity(format!("Unexpected additional argument: {:?}", arg)).into());
+    }
+
+    let values = list.into_iter().map(|value| value.deref().clone()).collect();
+    let sorted = match comparator {
+        Some(func) => {
+            merge_sort(values, &mut |a, b| call_predicate(&func, vec![a.clone(), b.clone()],
+                                                          &scope))?
+        },
+        None => merge_sort(values, &mut |a, b| Ok(a.compare(b)? == Ordering::Less))?
+    };
+
+    new_list(sorted, &scope)
+}
+
+fn any(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let (func, list) = function_and_list(args)?;
+
+    for value in list {
+        if call_predicate(&func, vec![value], &scope)? {
+            return Ok(Value::Boolean(true));
+        }
+    }
+
+    Ok(Value::Boolean(false))
+}
+
+fn every(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let (func, list) = function_and_list(args)?;
+
+    for value in list {
+        if !call_predicate(&func, vec![value], &scope)? {
+            return Ok(Value::Boolean(false));
+        }
+    }
+
+    Ok(Value::Boolean(true))
+}
+
+/// The first element of a list that satisfies a predicate, or nil.
+fn find(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let (func, list) = function_and_list(args)?;
+
+    for value in list {
+        if call_predicate(&func, vec![value.clone()], &scope)? {
+            return Ok(value);
+        }
+    }
+
+    Ok(Value::Nil)
+}
+
+fn expect_vector(value: Value) -> Result<Rc<RefCell<Vec<Value>>>> {
+    match value {
+        Value::Vector(values) => Ok(values),
+        value => Err(ErrorKind::Type(format!("Expected a vector, got: {:?}", value)).into())
+    }
+}
+
+fn vector_index(index: Value, len: usize) -> Result<usize> {
+    match index {
+        Value::Integer(n) if n >= 0 && (n as u64) < len as u64 => Ok(n as usize),
+        Value::Integer(n) => {
+            Err(ErrorKind::Type(format!("Index {} out of range for vector of length {}", n, len))
+                .into())
+        },
+        index => Err(ErrorKind::Type(format!("Expected an integer index, got: {:?}", index)).into())
+    }
+}
+
+/// Makes a vector, counting its elements against the scope's limits.
+fn new_vector(values: Vec<Value>, scope: &Scope) -> Result<Value> {
+    scope.budget().allocate_cons_cells(values.len())?;
+    Ok(Value::vector(values))
+}
+
+fn vector(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_vector(args, &scope)
+}
+
+fn vector_ref(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (vector, index) = two_args(args)?;
+    let values = expect_vector(vector)?;
+    let values = values.borrow();
+    Ok(values[vector_index(index, values.len())?].clone())
+}
+
+fn vector_set(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    if args.len() != 3 {
+        return Err(ErrorKind::Arity("Expected a vector, an index and a value".to_string())
+                   .into());
+    }
+
+    let mut iter = args.into_iter();
+    let values = expect_vector(iter.next().unwrap())?;
+    let index = vector_index(iter.next().unwrap(), values.borrow().len())?;
+    values.borrow_mut()[index] = iter.next().unwrap();
+    Ok(Value::Nil)
+}
+
+fn vector_push(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let (vector, value) = two_args(args)?;
+    let values = expect_vector(vector)?;
+    scope.budget().allocate_cons_cells(1)?;
+    values.borrow_mut().push(value);
+    Ok(Value::Nil)
+}
+
+fn vector_length(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    Ok(Value::Integer(expect_vector(one_arg(args)?)?.borrow().len() as i64))
+}
+
+fn vector_to_list(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let values = expect_vector(one_arg(args)?)?.borrow().clone();
+    new_list(values, &scope)
+}
+
+fn list_to_vector(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let values = proper_list(&one_arg(args)?)?.into_iter()
+        .map(|value| value.deref().clone())
+        .collect();
+    new_vector(values, &scope)
+}
+
+type Map = collections::HashMap<HashKey, Value>;
+
+fn expect_hash_map(value: Value) -> Result<Rc<RefCell<Map>>> {
+    match value {
+        Value::HashMap(map) => Ok(map),
+        value => Err(ErrorKind::Type(format!("Expected a hash table, got: {:?}", value)).into())
+    }
+}
+
+fn hash_map(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if !args.len().is_multiple_of(2) {
+        return Err(ErrorKind::Arity("Uneven key and value pairs".to_string()).into());
+    }
+
+    scope.budget().allocate_cons_cells(args.len() / 2)?;
+
+    let map = args.into_iter()
+        .tuples()
+        .map(|(key, value)| Ok((HashKey::from_value(key)?, value)))
+        .collect::<Result<_>>()?;
+
+    Ok(Value::hash_map(map))
+}
+
+/// Looks up a key, giving the default (or nil) if it isn't present.
+fn hash_get(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(ErrorKind::Arity("Expected a hash table, a key and an optional default"
+                                    .to_string()).into());
+    }
+
+    let mut iter = args.into_iter();
+    let map = expect_hash_map(iter.next().unwrap())?;
+    let key = HashKey::from_value(iter.next().unwrap())?;
+    let value = map.borrow().get(&key).cloned();
+    Ok(value.or_else(|| iter.next()).unwrap_or(Value::Nil))
+}
+
+fn hash_set(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    if args.len() != 3 {
+        return Err(ErrorKind::Arity("Expected a hash table, a key and a value".to_string())
+                   .into());
+    }
+
+    let mut iter = args.into_iter();
+    let map = expect_hash_map(iter.next().unwrap())?;
+    let key = HashKey::from_value(iter.next().unwrap())?;
+
+    if !map.borrow().contains_key(&key) {
+        scope.budget().allocate_cons_cells(1)?;
+    }
+
+    map.borrow_mut().insert(key, iter.next().unwrap());
+    Ok(Value::Nil)
+}
+
+/// Removes a key, returning whether it was present.
+fn hash_remove(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (map, key) = two_args(args)?;
+    let removed = expect_hash_map(map)?.borrow_mut().remove(&HashKey::from_value(key)?);
+    Ok(Value::Boolean(removed.is_some()))
+}
+
+fn hash_contains(args: Vec<Value>, _scope: Rc<Scope>) -> Result<Value> {
+    let (map, key) = two_args(args)?;
+    let contains = expect_hash_map(map)?.borrow().contains_key(&HashKey::from_value(key)?);
+    Ok(Value::Boolean(contains))
+}
+
+fn hash_entries(args: Vec<Value>) -> Result<Vec<(Value, Value)>> {
+    let map = expect_hash_map(one_arg(args)?)?;
+    let entries = Value::sorted_entries(&map.borrow());
+    Ok(entries)
+}
+
+/// The keys of a hash table, ordered by type and then by value, as are the
+/// results of `hash-values` and `hash->alist`.
+fn hash_keys(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_list(hash_entries(args)?.into_iter().map(|(key, _)| key).collect(), &scope)
+}
+
+fn hash_values(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    new_list(hash_entries(args)?.into_iter().map(|(_, value)| value).collect(), &scope)
+}
+
+fn hash_to_alist(args: Vec<Value>, scope: Rc<Scope>) -> Result<Value> {
+    let entries = hash_entries(args)?;
+    scope.budget().allocate_cons_cells(entries.len())?;
+
+    let pairs = entries.into_iter()
+        .map(|(key, value)| Value::cons(key, value))
+        .collect();
+
+    new_list(pairs, &scope)
+}
+
+/// Registers the functions that make up a capability.
+pub fn register(scope: &mut HashMap<String, Value>, capability: Capability) {
+    match capability {
+        Capability::Core => register_core(scope),
+        Capability::Math => register_math(scope),
+        Capability::Strings => register_strings(scope),
+        Capability::Io => register_io(scope),
+        Capability::Filesystem => register_filesystem(scope),
+        Capability::Process => register_process(scope),
+        Capability::Time => register_time(scope)
+    }
+}
+
+fn register_core(scope: &mut HashMap<String, Value>) {
+    scope.insert("error".to_string(),
+                 Value::NativeFunction("error".to_string(), error));
     scope.insert("list".to_string(),
                  Value::NativeFunction("list".to_string(), list));
     scope.insert("append".to_string(),
                  Value::NativeFunction("append".to_string(), append));
+    scope.insert("cons".to_string(),
+                 Value::NativeFunction("cons".to_string(), cons));
+    scope.insert("car".to_string(),
+                 Value::NativeFunction("car".to_string(), car));
+    scope.insert("first".to_string(),
+                 Value::NativeFunction("first".to_string(), car));
+    scope.insert("cdr".to_string(),
+                 Value::NativeFunction("cdr".to_string(), cdr));
+    scope.insert("rest".to_string(),
+                 Value::NativeFunction("rest".to_string(), cdr));
+    scope.insert("nth".to_string(),
+                 Value::NativeFunction("nth".to_string(), nth));
+    scope.insert("length".to_string(),
+                 Value::NativeFunction("length".to_string(), length));
+    scope.insert("reverse".to_string(),
+                 Value::NativeFunction("reverse".to_string(), reverse));
+    scope.insert("last".to_string(),
+                 Value::NativeFunction("last".to_string(), last));
+    scope.insert("null?".to_string(),
+                 Value::NativeFunction("null?".to_string(), is_null));
+    scope.insert("list?".to_string(),
+                 Value::NativeFunction("list?".to_string(), is_list));
+    scope.insert("pair?".to_string(),
+                 Value::NativeFunction("pair?".to_string(), is_pair));
+    scope.insert("funcall".to_string(),
+                 Value::NativeFunction("funcall".to_string(), funcall));
+    scope.insert("apply".to_string(),
+                 Value::NativeFunction("apply".to_string(), apply));
+    scope.insert("map".to_string(),
+                 Value::NativeFunction("map".to_string(), map));
+    scope.insert("filter".to_string(),
+                 Value::NativeFunction("filter".to_string(), filter));
+    scope.insert("reduce".to_string(),
+                 Value::NativeFunction("reduce".to_string(), reduce));
+    scope.insert("fold".to_string(),
+                 Value::NativeFunction("fold".to_string(), fold));
+    scope.insert("sort".to_string(),
+                 Value::NativeFunction("sort".to_string(), sort));
+    scope.insert("any".to_string(),
+                 Value::NativeFunction("any".to_string(), any));
+    scope.insert("every".to_string(),
+                 Value::NativeFunction("every".to_string(), every));
+    scope.insert("find".to_string(),
+                 Value::NativeFunction("find".to_string(), find));
+    scope.insert("vector".to_string(),
+                 Value::NativeFunction("vector".to_string(), vector));
+    scope.insert("vector-ref".to_string(),
+                 Value::NativeFunction("vector-ref".to_string(), vector_ref));
+    scope.insert("vector-set!".to_string(),
+                 Value::NativeFunction("vector-set!".to_string(), vector_set));
+    scope.insert("vector-push!".to_string(),
+                 Value::NativeFunction("vector-push!".to_string(), vector_push));
+    scope.insert("vector-length".to_string(),
+                 Value::NativeFunction("vector-length".to_string(), vector_length));
+    scope.insert("vector->list".to_string(),
+                 Value::NativeFunction("vector->list".to_string(), vector_to_list));
+    scope.insert("list->vector".to_string(),
+                 Value::NativeFunction("list->vector".to_string(), list_to_vector));
+    scope.insert("hash-map".to_string(),
+                 Value::NativeFunction("hash-map".to_string(), hash_map));
+    scope.insert("hash-get".to_string(),
+                 Value::NativeFunction("hash-get".to_string(), hash_get));
+    scope.insert("hash-set!".to_string(),
+                 Value::NativeFunction("hash-set!".to_string(), hash_set));
+    scope.insert("hash-remove!".to_string(),
+                 Value::NativeFunction("hash-remove!".to_string(), hash_remove));
+    scope.insert("hash-contains?".to_string(),
+                 Value::NativeFunction("hash-contains?".to_string(), hash_contains));
+    scope.insert("hash-keys".to_string(),
+                 Value::NativeFunction("hash-keys".to_string(), hash_keys));
+    scope.insert("hash-values".to_string(),
+                 Value::NativeFunction("hash-values".to_string(), hash_values));
+    scope.insert("hash->alist".to_string(),
+                 Value::NativeFunction("hash->alist".to_string(), hash_to_alist));
     scope.insert("=".to_string(),
                  Value::NativeFunction("equal".to_string(), equal));
+}
+
+fn register_math(scope: &mut HashMap<String, Value>) {
     scope.insert("+".to_string(),
                  Value::NativeFunction("plus".to_string(), plus));
+    scope.insert("-".to_string(),
+                 Value::NativeFunction("minus".to_string(), minus));
+    scope.insert("*".to_string(),
+                 Value::NativeFunction("times".to_string(), times));
+    scope.insert("/".to_string(),
+                 Value::NativeFunction("divide".to_string(), divide));
+    scope.insert("mod".to_string(),
+                 Value::NativeFunction("mod".to_string(), modulo));
+    scope.insert("rem".to_string(),
+                 Value::NativeFunction("rem".to_string(), remainder));
+    scope.insert("abs".to_string(),
+                 Value::NativeFunction("abs".to_string(), abs));
+    scope.insert("min".to_string(),
+                 Value::NativeFunction("min".to_string(), min));
+    scope.insert("max".to_string(),
+                 Value::NativeFunction("max".to_string(), max));
+    scope.insert("floor".to_string(),
+                 Value::NativeFunction("floor".to_string(), floor));
+    scope.insert("ceil".to_string(),
+                 Value::NativeFunction("ceil".to_string(), ceil));
+    scope.insert("round".to_string(),
+                 Value::NativeFunction("round".to_string(), round));
+    scope.insert("sqrt".to_string(),
+                 Value::NativeFunction("sqrt".to_string(), sqrt));
+    scope.insert("expt".to_string(),
+                 Value::NativeFunction("expt".to_string(), expt));
+    scope.insert("<".to_string(),
+                 Value::NativeFunction("less".to_string(), less));
+    scope.insert(">".to_string(),
+                 Value::NativeFunction("greater".to_string(), greater));
+    scope.insert("<=".to_string(),
+                 Value::NativeFunction("less-or-equal".to_string(), less_or_equal));
+    scope.insert(">=".to_string(),
+                 Value::NativeFunction("greater-or-equal".to_string(), greater_or_equal));
+}
+
+fn register_strings(scope: &mut HashMap<String, Value>) {
+    scope.insert("string-length".to_string(),
+                 Value::NativeFunction("string-length".to_string(), string_length));
+    scope.insert("string-append".to_string(),
+                 Value::NativeFunction("string-append".to_string(), string_append));
+    scope.insert("substring".to_string(),
+                 Value::NativeFunction("substring".to_string(), substring));
+    scope.insert("string-split".to_string(),
+                 Value::NativeFunction("string-split".to_string(), string_split));
+    scope.insert("string-join".to_string(),
+                 Value::NativeFunction("string-join".to_string(), string_join));
+    scope.insert("string-trim".to_string(),
+                 Value::NativeFunction("string-trim".to_string(), string_trim));
+    scope.insert("string-upcase".to_string(),
+                 Value::NativeFunction("string-upcase".to_string(), string_upcase));
+    scope.insert("string-downcase".to_string(),
+                 Value::NativeFunction("string-downcase".to_string(), string_downcase));
+    scope.insert("string-index".to_string(),
+                 Value::NativeFunction("string-index".to_string(), string_index));
+    scope.insert("string-replace".to_string(),
+                 Value::NativeFunction("string-replace".to_string(), string_replace));
+    scope.insert("string->list".to_string(),
+                 Value::NativeFunction("string->list".to_string(), string_to_list));
+    scope.insert("string->number".to_string(),
+                 Value::NativeFunction("string->number".to_string(), string_to_number));
+    scope.insert("number->string".to_string(),
+                 Value::NativeFunction("number->string".to_string(), number_to_string));
+    scope.insert("string->symbol".to_string(),
+                 Value::NativeFunction("string->symbol".to_string(), string_to_symbol));
+    scope.insert("symbol->string".to_string(),
+                 Value::NativeFunction("symbol->string".to_string(), symbol_to_string));
+}
+
+fn register_io(scope: &mut HashMap<String, Value>) {
+    scope.insert("println".to_string(),
+                 Value::NativeFunction("println".to_string(), println));
+    scope.insert("read-line".to_string(),
+                 Value::NativeFunction("read-line".to_string(), read_line));
+}
+
+fn register_filesystem(scope: &mut HashMap<String, Value>) {
+    scope.insert("read-file".to_string(),
+                 Value::NativeFunction("read-file".to_string(), read_file));
+    scope.insert("write-file".to_string(),
+                 Value::NativeFunction("write-file".to_string(), write_file));
+    scope.insert("file-exists?".to_string(),
+                 Value::NativeFunction("file-exists?".to_string(), file_exists));
+}
+
+fn register_process(scope: &mut HashMap<String, Value>) {
+    scope.insert("exit".to_string(),
+                 Value::NativeFunction("exit".to_string(), exit));
+    scope.insert("getenv".to_string(),
+                 Value::NativeFunction("getenv".to_string(), getenv));
+}
+
+fn register_time(scope: &mut HashMap<String, Value>) {
+    scope.insert("current-time".to_string(),
+                 Value::NativeFunction("current-time".to_string(), current_time));
+    scope.insert("sleep".to_string(),
+                 Value::NativeFunction("sleep".to_string(), sleep));
 }
@@ -0,0 +1,85 @@
+use std::cmp::Ordering;
+use num_bigint::BigInt;
+use value::Value;
+use symbol::Symbol;
+use error::{ErrorKind, Result};
+
+/// A hash table key. Only values that can't be mutated and that compare
+/// equal exactly can be keys, so a key can't change after it's inserted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Boolean(bool),
+    Integer(i64),
+    BigInt(BigInt),
+    Char(char),
+    String(String),
+    Keyword(Symbol),
+    Symbol(Symbol)
+}
+
+impl HashKey {
+    pub fn from_value(value: Value) -> Result<HashKey> {
+        match value {
+            Value::Boolean(b) => Ok(HashKey::Boolean(b)),
+            Value::Integer(n) => Ok(HashKey::Integer(n)),
+            Value::BigInt(n) => Ok(HashKey::BigInt(n)),
+            Value::Char(c) => Ok(HashKey::Char(c)),
+            Value::String(s) => Ok(HashKey::String(s)),
+            Value::Keyword(name) => Ok(HashKey::Keyword(name)),
+            Value::Symbol(sym) => Ok(HashKey::Symbol(sym)),
+            value => {
+                Err(ErrorKind::Type(format!("Expected a string, character, integer, symbol, \
+                                             keyword or boolean key, got: {:?}", value)).into())
+            }
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            HashKey::Boolean(b) => Value::Boolean(*b),
+            HashKey::Integer(n) => Value::Integer(*n),
+            HashKey::BigInt(n) => Value::BigInt(n.clone()),
+            HashKey::Char(c) => Value::Char(*c),
+            HashKey::String(s) => Value::String(s.clone()),
+            HashKey::Keyword(name) => Value::Keyword(*name),
+            HashKey::Symbol(sym) => Value::Symbol(*sym)
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            HashKey::Boolean(_) => 0,
+            HashKey::Integer(_) | HashKey::BigInt(_) => 1,
+            HashKey::Char(_) => 2,
+            HashKey::String(_) => 3,
+            HashKey::Keyword(_) => 4,
+            HashKey::Symbol(_) => 5
+        }
+    }
+}
+
+impl Ord for HashKey {
+    /// Orders keys by type and then by value, so that hash tables are listed
+    /// and printed in a consistent order.
+    fn cmp(&self, other: &HashKey) -> Ordering {
+        use self::HashKey::*;
+
+        match (self, other) {
+            (Boolean(a), Boolean(b)) => a.cmp(b),
+            (Integer(a), Integer(b)) => a.cmp(b),
+            (BigInt(a), BigInt(b)) => a.cmp(b),
+            (Integer(a), BigInt(b)) => ::num_bigint::BigInt::from(*a).cmp(b),
+            (BigInt(a), Integer(b)) => a.cmp(&::num_bigint::BigInt::from(*b)),
+            (Char(a), Char(b)) => a.cmp(b),
+            (String(a), String(b)) => a.cmp(b),
+            (Keyword(a), Keyword(b)) | (Symbol(a), Symbol(b)) => a.as_str().cmp(b.as_str()),
+            (a, b) => a.rank().cmp(&b.rank())
+        }
+    }
+}
+
+impl PartialOrd for HashKey {
+    fn partial_cmp(&self, other: &HashKey) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,98 @@
+use std::rc::Rc;
+use value::Value;
+use scope::Scope;
+use symbol::Symbol;
+use convert::NativeFn;
+use error::Result;
+use limits::Limits;
+use capability::Capability;
+use interrupt::InterruptHandle;
+
+/// An interpreter whose global definitions persist between evaluations, so
+/// a library can be loaded once and its functions called repeatedly.
+pub struct Interpreter {
+    scope: Rc<Scope>
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter { scope: Scope::root() }
+    }
+
+    /// An interpreter whose evaluations each fail with a `LimitExceeded`
+    /// error if they use more than `limits` allow.
+    pub fn with_limits(limits: Limits) -> Self {
+        Interpreter { scope: Scope::root_with_limits(limits) }
+    }
+
+    /// An interpreter for untrusted scripts, with only the built-in functions
+    /// from `capabilities` and its evaluations checked against `limits`.
+    pub fn sandboxed(capabilities: &[Capability], limits: Limits) -> Self {
+        Interpreter { scope: Scope::root_with(capabilities, limits) }
+    }
+
+    /// The global scope that everything is evaluated in.
+    pub fn scope(&self) -> &Rc<Scope> {
+        &self.scope
+    }
+
+    pub fn eval_str(&self, expr: &str) -> Result<Value> {
+        self.eval_value(::read(expr)?)
+    }
+
+    /// Like `eval_str`, but with locations in errors referring to the given
+    /// file name.
+    pub fn eval_str_named(&self, expr: &str, name: &str) -> Result<Value> {
+        self.eval_value(::read_named(expr, name)?)
+    }
+
+    pub fn eval_value(&self, expr: Value) -> Result<Value> {
+        self.scope.budget().reset();
+        expr.eval(&self.scope)
+    }
+
+    pub fn define(&self, name: &str, value: Value) {
+        self.scope.set_value(Symbol::intern(name), value);
+    }
+
+    pub fn define_native<F>(&self, name: &str, func: F)
+        where F: Fn(Vec<Value>, Rc<Scope>) -> Result<Value> + 'static
+    {
+        self.scope.define_native(name, func);
+    }
+
+    /// Binds a plain Rust function, such as `fn(i64, String) -> Result<bool>`,
+    /// converting its arguments and result automatically.
+    pub fn define_fn<Args, F: NativeFn<Args>>(&self, name: &str, func: F) {
+        self.scope.define_fn(name, func);
+    }
+
+    /// A handle that stops whatever this interpreter is evaluating, which can
+    /// be sent to another thread or used from a signal handler.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.scope.budget().interrupt_handle().clone()
+    }
+
+    /// Stops scripts from redefining anything defined so far, such as `if`
+    /// or `+`, or functions the application has provided.
+    pub fn freeze(&self) {
+        self.scope.freeze();
+    }
+
+    pub fn get(&self, name: &str) -> Result<Value> {
+        self.scope.get_value(Symbol::intern(name))
+    }
+
+    /// Calls the function bound to `name` with arguments that are passed as
+    /// they are, without being evaluated.
+    pub fn call(&self, name: &str, args: Vec<Value>) -> Result<Value> {
+        self.scope.budget().reset();
+        self.get(name)?.invoke(args, &self.scope)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
@@ -1,65 +1,152 @@
-#![feature(arbitrary_self_types)]
-
 extern crate itertools;
 #[macro_use]
 extern crate nom;
-extern crate runtime_fmt;
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_traits;
+extern crate num_integer;
+#[macro_use]
+extern crate serde;
+
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
 
+mod error;
+mod location;
 mod expr;
 mod value;
+mod number;
 mod params;
 mod function;
 mod scope;
 mod parser;
 mod macros;
 mod functions;
+mod interpreter;
+mod limits;
+mod capability;
+mod interrupt;
+mod symbol;
+mod hash_key;
+mod convert;
+mod ser;
+mod de;
 
-pub use parser::parse;
+pub use parser::{parse, is_incomplete};
+pub use error::{Error, ErrorKind, Frame, Result};
+pub use location::Location;
+pub use value::{Value, Cdr};
+pub use scope::Scope;
+pub use function::NativeClosure;
+pub use interpreter::Interpreter;
+pub use limits::{Budget, Limits};
+pub use capability::Capability;
+pub use interrupt::InterruptHandle;
+pub use symbol::Symbol;
+pub use hash_key::HashKey;
+pub use convert::{FromValue, IntoValue, NativeFn};
+pub use ser::to_value;
+pub use de::from_value;
 
-use value::Value;
-use scope::Scope;
+use std::rc::Rc;
+use location::Source;
+
+pub fn read(expr: &str) -> Result<Value> {
+    read_source(Source::new(expr, None))
+}
+
+/// Like `read`, but with locations in errors referring to the given file name.
+pub fn read_named(expr: &str, name: &str) -> Result<Value> {
+    read_source(Source::new(expr, Some(name)))
+}
 
-pub fn read(expr: &str) -> Value {
-    let mut exprs: Vec<Value> = parse(expr)
-        .expect("Unable to parse input")
+fn read_source(source: Source) -> Result<Value> {
+    let mut exprs: Vec<Value> = parser::parse_source(&source)?
         .into_iter()
-        .map(|e| e.into_value())
-        .collect();
+        .map(|e| e.into_value(&source))
+        .collect::<Result<_>>()?;
 
-    if exprs.len() == 0 {
-        Value::Nil
+    if exprs.is_empty() {
+        Ok(Value::Nil)
     } else if exprs.len() == 1 {
-        exprs.remove(0)
+        Ok(exprs.remove(0))
     } else {
-        Value::progn(Value::list(exprs.into_iter()))
+        Ok(Value::progn(Value::list(exprs.into_iter())))
     }
 }
 
-pub fn read_and_eval(expr: &str) -> Value {
-    eval(read(expr))
+pub fn read_and_eval(expr: &str) -> Result<Value> {
+    eval(read(expr)?)
 }
 
-pub fn eval(expr: Value) -> Value {
-    expr.eval(&Scope::root())
+pub fn read_and_eval_named(expr: &str, name: &str) -> Result<Value> {
+    eval(read_named(expr, name)?)
+}
+
+pub fn eval(expr: Value) -> Result<Value> {
+    eval_in(expr, &Scope::root())
+}
+
+/// Evaluates in an existing scope, such as one with functions defined by the
+/// embedding application.
+pub fn eval_in(expr: Value, scope: &Rc<Scope>) -> Result<Value> {
+    expr.eval(scope)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use nom::IResult;
 
     #[test]
     pub fn eval_set() {
         assert_eq!(read_and_eval("(set a 2 b 3)\
                                   (+ a b)"),
-                   Value::Integer(5));
+                   Ok(Value::Integer(5)));
+    }
+
+    #[test]
+    pub fn eval_define() {
+        assert_eq!(read_and_eval("(define a 2)\
+                                  (let ((b 3))\
+                                    (define a 10))\
+                                  (+ a 1)"),
+                   Ok(Value::Integer(3)));
+    }
+
+    #[test]
+    pub fn eval_assign() {
+        assert_eq!(read_and_eval("(set a 2)\
+                                  (let ((b 3))\
+                                    (set! a (+ a b)))\
+                                  a"),
+                   Ok(Value::Integer(5)));
+    }
+
+    #[test]
+    pub fn eval_assign_closure_state() {
+        assert_eq!(read_and_eval("(defun make-counter ()\
+                                    (let ((count 0))\
+                                      (lambda ()\
+                                        (set! count (+ count 1))\
+                                        count)))\
+                                  (set counter (make-counter))\
+                                  (counter)\
+                                  (counter)"),
+                   Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    pub fn eval_assign_unbound() {
+        assert_eq!(read_and_eval("(set! missing 1)").unwrap_err().kind(),
+                   &ErrorKind::UnboundSymbol("missing".to_string()));
     }
 
     #[test]
     pub fn eval_let() {
         assert_eq!(read_and_eval("(let ((a 2) (b 3))\
                                     (+ a b))"),
-                   Value::Integer(5));
+                   Ok(Value::Integer(5)));
     }
 
     #[test]
@@ -68,7 +155,7 @@ mod tests {
                                   (let ((a 2)\
                                         (b 3))\
                                     (+ a b)) (+ a b)"),
-                   Value::Integer(30));
+                   Ok(Value::Integer(30)));
     }
 
     #[test]
@@ -76,18 +163,1032 @@ mod tests {
         assert_eq!(read_and_eval("(defun plus (a b)\
                                     (+ a b))\
                                   (plus 4 6)"),
-                   Value::Integer(10));
+                   Ok(Value::Integer(10)));
+    }
+
+    #[test]
+    pub fn eval_lambda() {
+        assert_eq!(read_and_eval("((lambda (a (b 2)) (+ a b)) 4)"),
+                   Ok(Value::Integer(6)));
+    }
+
+    #[test]
+    pub fn eval_lambda_closure() {
+        assert_eq!(read_and_eval("(defun adder (a)\
+                                    (lambda (b) (+ a b)))\
+                                  (set add-two (adder 2))\
+                                  (add-two 5)"),
+                   Ok(Value::Integer(7)));
+    }
+
+    #[test]
+    pub fn eval_lambda_is_anonymous() {
+        assert_eq!(read_and_eval("(lambda (a) a)").map(|f| format!("{:?}", f)),
+                   Ok("<function anonymous>".to_string()));
+        assert_eq!(read_and_eval("(lambda (a) a) a").unwrap_err().kind(),
+                   &ErrorKind::UnboundSymbol("a".to_string()));
+    }
+
+    #[test]
+    pub fn eval_tail_calls() {
+        assert_eq!(read_and_eval("(defun countdown (n)\
+                                    (if (= n 0)\
+                                      'done\
+                                      (let ((next (+ n -1)))\
+                                        (progn (countdown next)))))\
+                                  (countdown 100000)"),
+                   Ok(Value::symbol("done")));
     }
 
     #[test]
     pub fn eval_addition() {
         assert_eq!(read_and_eval("(+ 1 2.4)"),
-                   Value::Float(3.4));
+                   Ok(Value::Float(3.4)));
     }
 
     #[test]
     pub fn eval_nested_addition() {
         assert_eq!(read_and_eval("(+ (+ 3.1 1) 2.4)"),
-                   Value::Float(6.5));
+                   Ok(Value::Float(6.5)));
+    }
+
+    #[test]
+    pub fn eval_unbound_symbol() {
+        assert_eq!(read_and_eval("(+ 1 missing)").unwrap_err().kind(),
+                   &ErrorKind::UnboundSymbol("missing".to_string()));
+    }
+
+    #[test]
+    pub fn eval_type_error() {
+        assert_eq!(read_and_eval("(+ 1 \"two\")").unwrap_err().kind(),
+                   &ErrorKind::Type("Unable to add 1 and \"two\"".to_string()));
+        assert_eq!(read_and_eval("(+ \"a\")").unwrap_err().kind(),
+                   &ErrorKind::Type("Expected number, got: \"a\"".to_string()));
+        assert_eq!(read_and_eval("(+ 2)"), Ok(Value::Integer(2)));
+        assert_eq!(read_and_eval("(+)").unwrap_err().kind(),
+                   &ErrorKind::Arity("Expected at least one argument".to_string()));
+    }
+
+    #[test]
+    pub fn eval_arity_error() {
+        assert_eq!(read_and_eval("(defun plus (a b)\
+                                    (+ a b))\
+                                  (plus 4)").unwrap_err().kind(),
+                   &ErrorKind::Arity("Missing required arguments: b".to_string()));
+    }
+
+    #[test]
+    pub fn eval_user_error() {
+        assert_eq!(read_and_eval("(error \"Something went wrong\")").unwrap_err().kind(),
+                   &ErrorKind::User("Something went wrong".to_string()));
+    }
+
+    #[test]
+    pub fn read_parse_error() {
+        assert!(matches!(read("(+ 1 2").unwrap_err().kind(), ErrorKind::Parse(_)));
+    }
+
+    #[test]
+    pub fn error_location() {
+        let error = read_and_eval_named("(defun f (x)\n  (+ x 1))\n\n(f\n  (g 2))",
+                                        "script.lisp").unwrap_err();
+        assert_eq!(error.to_string().lines().next(),
+                   Some("script.lisp:5:3: Symbol not found: g"));
+    }
+
+    #[test]
+    pub fn error_location_in_function_body() {
+        let error = read_and_eval_named("(defun f (x)\n  (+ x \"one\"))\n(f 1)",
+                                        "script.lisp").unwrap_err();
+        assert_eq!(error.location().map(|l| (l.line, l.column)), Some((2, 3)));
+    }
+
+    #[test]
+    pub fn parse_error_location() {
+        let error = read_named("(+ 1 2)\n  (+ 3", "script.lisp").unwrap_err();
+        assert_eq!(error.location().map(|l| (l.line, l.column)), Some((2, 3)));
+    }
+
+    #[test]
+    pub fn error_backtrace() {
+        let error = read_and_eval_named("(defun inner (x)\n  (+ x \"one\"))\n\
+                                         (defun outer (x)\n  (list (inner x)))\n\
+                                         (outer 1)",
+                                        "script.lisp").unwrap_err();
+        let frames: Vec<(&str, Option<usize>)> = error.backtrace().iter()
+            .map(|frame| (frame.name.as_str(), frame.location.as_ref().map(|l| l.line)))
+            .collect();
+
+        assert_eq!(frames, vec![("plus", Some(2)),
+                                ("inner", Some(4)),
+                                ("list", Some(4)),
+                                ("outer", Some(5))]);
+    }
+
+    #[test]
+    pub fn error_backtrace_omits_tail_calls() {
+        let error = read_and_eval("(defun inner (x) (+ x \"one\"))\
+                                   (defun outer (x) (inner x))\
+                                   (outer 1)").unwrap_err();
+        let frames: Vec<&str> = error.backtrace().iter()
+            .map(|frame| frame.name.as_str())
+            .collect();
+
+        assert_eq!(frames, vec!["plus", "inner"]);
+    }
+
+    #[test]
+    pub fn error_backtrace_is_capped() {
+        let error = read_and_eval("(defun countdown (n)\
+                                     (if (= n 0)\
+                                       (error \"Done\")\
+                                       (list (countdown (+ n -1)))))\
+                                   (countdown 50)").unwrap_err();
+        let message = error.to_string();
+
+        assert!(error.backtrace().len() > 50);
+        assert_eq!(message.lines().count(), 22);
+        assert!(message.ends_with(&format!("... {} more", error.backtrace().len() - 20)));
+    }
+
+    #[test]
+    pub fn eval_arithmetic() {
+        assert_eq!(read_and_eval("(- 10 2 3)"), Ok(Value::Integer(5)));
+        assert_eq!(read_and_eval("(- 4)"), Ok(Value::Integer(-4)));
+        assert_eq!(read_and_eval("(* 2 3 4)"), Ok(Value::Integer(24)));
+        assert_eq!(read_and_eval("(* 2 1.5)"), Ok(Value::Float(3.0)));
+        assert_eq!(read_and_eval("(/ 12 2 3)"), Ok(Value::Integer(2)));
+        assert_eq!(read_and_eval("(/ 7.0 2)"), Ok(Value::Float(3.5)));
+        assert_eq!(read_and_eval("(mod -7 3)"), Ok(Value::Integer(2)));
+        assert_eq!(read_and_eval("(rem -7 3)"), Ok(Value::Integer(-1)));
+        assert_eq!(read_and_eval("(mod 7.5 -2)"), Ok(Value::Float(-0.5)));
+    }
+
+    #[test]
+    pub fn eval_numeric_functions() {
+        assert_eq!(read_and_eval("(abs -3)"), Ok(Value::Integer(3)));
+        assert_eq!(read_and_eval("(abs -3.5)"), Ok(Value::Float(3.5)));
+        assert_eq!(read_and_eval("(min 3 1.5 2)"), Ok(Value::Float(1.5)));
+        assert_eq!(read_and_eval("(max 3 1.5 2)"), Ok(Value::Integer(3)));
+        assert_eq!(read_and_eval("(floor -2.5)"), Ok(Value::Integer(-3)));
+        assert_eq!(read_and_eval("(ceil 2.1)"), Ok(Value::Integer(3)));
+        assert_eq!(read_and_eval("(round 2.5)"), Ok(Value::Integer(3)));
+        assert_eq!(read_and_eval("(floor 4)"), Ok(Value::Integer(4)));
+        assert_eq!(read_and_eval("(sqrt 16)"), Ok(Value::Integer(4)));
+        assert_eq!(read_and_eval("(sqrt 2.25)"), Ok(Value::Float(1.5)));
+        assert_eq!(read_and_eval("(expt 2 10)"), Ok(Value::Integer(1024)));
+        assert_eq!(read_and_eval("(expt 2 -1.0)"), Ok(Value::Float(0.5)));
+    }
+
+    #[test]
+    pub fn eval_comparisons() {
+        assert_eq!(read_and_eval("(< 1 2 3)"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(< 1 3 2)"), Ok(Value::Boolean(false)));
+        assert_eq!(read_and_eval("(> 3 2.5 1)"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(<= 1 1 2)"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(>= 1 1 2)"), Ok(Value::Boolean(false)));
+        assert!(matches!(read_and_eval("(< 1 \"two\")").unwrap_err().kind(), ErrorKind::Type(_)));
+    }
+
+    #[test]
+    pub fn eval_arithmetic_errors() {
+        let is_arithmetic_error = |expr| matches!(read_and_eval(expr).unwrap_err().kind(),
+                                                  ErrorKind::Arithmetic(_));
+
+        assert!(is_arithmetic_error("(/ 1 0)"));
+        assert!(is_arithmetic_error("(/ 1/2 0)"));
+        assert!(is_arithmetic_error("(expt 0 -1)"));
+        assert!(is_arithmetic_error("(expt 2 4000000000)"));
+        assert!(is_arithmetic_error("(expt 3/2 -4000000000)"));
+        assert!(is_arithmetic_error("(expt 12345678901234567890 100000)"));
+        assert!(is_arithmetic_error("(floor (/ 1.0 0.0))"));
+        assert!(is_arithmetic_error("(/ 1.5 0.0)"));
+        assert!(is_arithmetic_error("(mod 1 0)"));
+        assert!(is_arithmetic_error("(sqrt -1)"));
+    }
+
+    fn eval_to_string(expr: &str) -> String {
+        format!("{:?}", read_and_eval(expr).unwrap())
+    }
+
+    #[test]
+    pub fn eval_exit() {
+        let exit_status = |expr| match read_and_eval(expr).unwrap_err().kind() {
+            ErrorKind::Exit(status) => Some(*status),
+            _ => None
+        };
+
+        assert_eq!(exit_status("(exit)"), Some(0));
+        assert_eq!(exit_status("(defun f () (exit 3) 4) (list (f))"), Some(3));
+        assert_eq!(exit_status("(exit 0)"), Some(0));
+        assert_eq!(exit_status("(exit 255)"), Some(255));
+        assert_eq!(exit_status("(exit -1)"), None);
+        assert_eq!(exit_status("(exit 256)"), None);
+        assert!(matches!(read_and_eval("(exit 256)").unwrap_err().kind(), ErrorKind::Type(_)));
+        assert_eq!(exit_status("(exit \"3\")"), None);
+        assert_eq!(exit_status("(exit 1 2)"), None);
+    }
+
+    #[test]
+    pub fn eval_println() {
+        let is_format_error = |expr| matches!(read_and_eval(expr).unwrap_err().kind(),
+                                              ErrorKind::Type(_));
+
+        assert_eq!(read_and_eval("(println \"{} {:?} {0} {{}}\" \"a\" \"b\")"), Ok(Value::Nil));
+        assert_eq!(read_and_eval("(println \"no arguments\")"), Ok(Value::Nil));
+        assert!(is_format_error("(println \"{}\")"));
+        assert!(is_format_error("(println \"{1}\" 1)"));
+        assert!(is_format_error("(println \"{:x}\" 1)"));
+        assert!(is_format_error("(println \"{\" 1)"));
+        assert!(is_format_error("(println \"}\")"));
+        assert!(is_format_error("(println 1)"));
+        assert!(read_and_eval("(println)").is_err());
+    }
+
+    #[test]
+    pub fn eval_macroexpand() {
+        assert_eq!(eval_to_string("(defmacro unless (c body) `(if ,c nil ,body)) \
+                                   (macroexpand (unless false (f)))"),
+                   "(if false nil (f))");
+        assert_eq!(eval_to_string("(macroexpand (undefined 1))"), "(undefined 1)");
+    }
+
+    fn eval_to_string_in(expr: &str, scope: &Rc<Scope>) -> String {
+        format!("{:?}", eval_in(read(expr).unwrap(), scope).unwrap())
+    }
+
+    #[test]
+    pub fn eval_big_integers() {
+        assert_eq!(eval_to_string("(+ 9223372036854775807 1)"), "9223372036854775808");
+        assert_eq!(eval_to_string("(* 4611686018427387904 2)"), "9223372036854775808");
+        assert_eq!(eval_to_string("(- -9223372036854775807 2)"), "-9223372036854775809");
+        assert_eq!(eval_to_string("(abs -9223372036854775808)"), "9223372036854775808");
+        assert_eq!(eval_to_string("(expt 2 100)"), "1267650600228229401496703205376");
+        assert_eq!(eval_to_string("(expt 1 4000000000)"), "1");
+        assert_eq!(eval_to_string("(expt -1 4000000001)"), "-1");
+        assert_eq!(eval_to_string("(= (expt 2 100000) (* (expt 2 50000) (expt 2 50000)))"),
+                   "true");
+        assert_eq!(eval_to_string("(- 100000000000000000000 99999999999999999999)"), "1");
+        assert_eq!(read_and_eval("(- 100000000000000000000 99999999999999999999)"),
+                   Ok(Value::Integer(1)));
+        assert_eq!(read_and_eval("(< 9223372036854775807 100000000000000000000)"),
+                   Ok(Value::Boolean(true)));
+        assert_eq!(eval_to_string("(sqrt 100000000000000000000)"), "10000000000");
+        assert_eq!(eval_to_string("(mod -100000000000000000000 3)"), "2");
+    }
+
+    #[test]
+    pub fn eval_ratios() {
+        assert_eq!(eval_to_string("(/ 1 3)"), "1/3");
+        assert_eq!(eval_to_string("(/ 4)"), "1/4");
+        assert_eq!(eval_to_string("(/ 7 2)"), "7/2");
+        assert_eq!(eval_to_string("(+ (/ 1 3) (/ 2 3))"), "1");
+        assert_eq!(read_and_eval("(* 3 (/ 1 3))"), Ok(Value::Integer(1)));
+        assert_eq!(eval_to_string("(- 1/2 1/3)"), "1/6");
+        assert_eq!(eval_to_string("4/6"), "2/3");
+        assert_eq!(eval_to_string("-1/2"), "-1/2");
+        assert_eq!(eval_to_string("(expt 2 -1)"), "1/2");
+        assert_eq!(eval_to_string("(expt 2/3 2)"), "4/9");
+        assert_eq!(eval_to_string("(sqrt 9/4)"), "3/2");
+        assert_eq!(eval_to_string("(floor -7/2)"), "-4");
+        assert_eq!(eval_to_string("(round 5/2)"), "3");
+        assert_eq!(eval_to_string("(mod 7/2 2)"), "3/2");
+        assert_eq!(read_and_eval("(+ 1/2 0.25)"), Ok(Value::Float(0.75)));
+        assert_eq!(read_and_eval("(< 1/3 0.34 1/2)"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(= 2/4 1/2)"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    pub fn eval_list_functions() {
+        assert_eq!(eval_to_string("(cons 1 (list 2 3))"), "(1 2 3)");
+        assert_eq!(eval_to_string("(cons 1 2)"), "(1 . 2)");
+        assert_eq!(eval_to_string("(car '(1 2 3))"), "1");
+        assert_eq!(eval_to_string("(first '(1 2 3))"), "1");
+        assert_eq!(eval_to_string("(cdr '(1 2 3))"), "(2 3)");
+        assert_eq!(eval_to_string("(rest '(1))"), "nil");
+        assert_eq!(eval_to_string("(car nil)"), "nil");
+        assert_eq!(eval_to_string("(nth 1 '(a b c))"), "b");
+        assert_eq!(eval_to_string("(nth 3 '(a b c))"), "nil");
+        assert_eq!(eval_to_string("(length '(a b c))"), "3");
+        assert_eq!(eval_to_string("(length nil)"), "0");
+        assert_eq!(eval_to_string("(reverse '(1 2 3))"), "(3 2 1)");
+        assert_eq!(eval_to_string("(last '(1 2 3))"), "3");
+        assert_eq!(eval_to_string("(last nil)"), "nil");
+        assert_eq!(read_and_eval("(null? nil)"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(null? '(1))"), Ok(Value::Boolean(false)));
+        assert_eq!(read_and_eval("(list? '(1 2))"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(list? nil)"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(list? (cons 1 2))"), Ok(Value::Boolean(false)));
+        assert_eq!(read_and_eval("(pair? (cons 1 2))"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(pair? nil)"), Ok(Value::Boolean(false)));
+    }
+
+    #[test]
+    pub fn eval_improper_list_errors() {
+        let type_error = |expr| match read_and_eval(expr).unwrap_err().kind() {
+            ErrorKind::Type(msg) => msg.clone(),
+            kind => panic!("Expected a type error, got: {:?}", kind)
+        };
+
+        assert_eq!(type_error("(length (cons 1 2))"), "Not a proper list: (1 . 2)");
+        assert_eq!(type_error("(reverse 5)"), "Not a proper list: 5");
+        assert_eq!(type_error("(nth 0 (cons 1 2))"), "Not a proper list: (1 . 2)");
+        assert_eq!(type_error("(car 5)"), "Expected a list, got: 5");
+        assert_eq!(type_error("(nth -1 '(1))"), "Expected a non-negative integer index, got: -1");
+        assert_eq!(type_error("(defmacro improper () (cons '+ (cons 1 2))) (improper)"),
+                   "Not a proper list: (1 . 2)");
+        assert_eq!(type_error("(let 5 nil)"), "Not a proper list: 5");
+    }
+
+    #[test]
+    pub fn eval_higher_order_functions() {
+        assert_eq!(eval_to_string("(funcall + 1 2)"), "3");
+        assert_eq!(eval_to_string("(apply + 1 '(2 3))"), "6");
+        assert_eq!(eval_to_string("(apply list nil)"), "nil");
+        assert_eq!(eval_to_string("(map (lambda (x) (* x x)) '(1 2 3))"), "(1 4 9)");
+        assert_eq!(eval_to_string("(map + '(1 2 3) '(10 20))"), "(11 22)");
+        assert_eq!(eval_to_string("(map car '((a b) (c d)))"), "(a c)");
+        assert_eq!(eval_to_string("(filter (lambda (x) (> x 1)) '(1 2 3))"), "(2 3)");
+        assert_eq!(eval_to_string("(reduce + '(1 2 3 4))"), "10");
+        assert_eq!(eval_to_string("(fold (lambda (acc x) (cons x acc)) nil '(1 2 3))"),
+                   "(3 2 1)");
+        assert_eq!(eval_to_string("(sort '(3 1 2))"), "(1 2 3)");
+        assert_eq!(eval_to_string("(sort '(3 1 2) >)"), "(3 2 1)");
+        assert_eq!(eval_to_string("(sort '((b 1) (a 2) (c 1)) \
+                                         (lambda (x y) (< (nth 1 x) (nth 1 y))))"),
+                   "((b 1) (c 1) (a 2))");
+        assert_eq!(read_and_eval("(any (lambda (x) (> x 2)) '(1 2 3))"), Ok(Value::Boolean(true)));
+        assert_eq!(read_and_eval("(every (lambda (x) (> x 2)) '(1 2 3))"),
+                   Ok(Value::Boolean(false)));
+        assert_eq!(read_and_eval("(every null? nil)"), Ok(Value::Boolean(true)));
+        assert_eq!(eval_to_string("(find (lambda (x) (> x 1)) '(1 2 3))"), "2");
+        assert_eq!(eval_to_string("(find null? '(1 2 3))"), "nil");
+        assert_eq!(eval_to_string("(defun twice (f x) (funcall f (funcall f x))) \
+                                   (twice (lambda (x) (* x 10)) 3)"),
+                   "300");
+    }
+
+    #[test]
+    pub fn higher_order_function_errors() {
+        let error = read_and_eval_named("(map (lambda (x) (+ x 1))\n     '(1 two))",
+                                        "script.lisp").unwrap_err();
+        let message = error.to_string();
+        let mut lines = message.lines();
+        assert_eq!(lines.next(), Some("script.lisp:1:18: Unable to add two and 1"));
+        assert_eq!(lines.next(), Some("    in plus at script.lisp:1:18"));
+        assert_eq!(lines.next(), Some("    in anonymous"));
+        assert_eq!(lines.next(), Some("    in map at script.lisp:1:1"));
+
+        assert!(read_and_eval("(filter (lambda (x) x) '(1))").is_err());
+        assert!(read_and_eval("(reduce + nil)").is_err());
+        assert!(read_and_eval("(funcall if true 1 2)").is_err());
+    }
+
+    #[test]
+    pub fn eval_native_closure() {
+        use std::cell::Cell;
+
+        let counter = Rc::new(Cell::new(0));
+        let scope = Scope::root();
+        let count = counter.clone();
+
+        scope.define_native("count", move |args, _scope| {
+            count.set(count.get() + args.len() as i64);
+            Ok(Value::Integer(count.get()))
+        });
+
+        assert_eq!(eval_in(read("(count 1 2) (count 3)").unwrap(), &scope),
+                   Ok(Value::Integer(3)));
+        assert_eq!(eval_in(read("(map count '(a b))").unwrap(), &scope),
+                   read("'(4 5)").and_then(eval));
+        assert_eq!(counter.get(), 5);
+        assert_eq!(eval_to_string_in("count", &scope), "<function count>");
+        assert_eq!(eval_in(read("(= count count)").unwrap(), &scope),
+                   Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    pub fn native_closure_errors() {
+        let scope = Scope::root();
+
+        scope.define_native("fail", |_args, _scope| {
+            Err(ErrorKind::User("Failed in Rust".to_string()).into())
+        });
+
+        let error = eval_in(read_named("(fail)", "script.lisp").unwrap(), &scope).unwrap_err();
+        assert_eq!(error.to_string(),
+                   "script.lisp:1:1: Failed in Rust\n    in fail at script.lisp:1:1");
+    }
+
+    #[test]
+    pub fn interpreter_keeps_definitions() {
+        let interpreter = Interpreter::new();
+
+        interpreter.eval_str("(defun square (x) (* x x)) (set total 0)").unwrap();
+        interpreter.eval_str("(set! total (+ total (square 3)))").unwrap();
+        assert_eq!(interpreter.eval_str("total"), Ok(Value::Integer(9)));
+        assert_eq!(interpreter.get("total"), Ok(Value::Integer(9)));
+        assert_eq!(interpreter.call("square", vec![Value::Integer(4)]), Ok(Value::Integer(16)));
+        assert_eq!(interpreter.call("list", vec![Value::symbol("a")]),
+                   interpreter.eval_str("'(a)"));
+    }
+
+    #[test]
+    pub fn interpreter_define() {
+        let interpreter = Interpreter::new();
+
+        interpreter.define("greeting", Value::String("hello".to_string()));
+        interpreter.define_native("shout", |args, _scope| {
+            Ok(Value::String(args[0].clone().as_string()?.to_uppercase()))
+        });
+
+        assert_eq!(interpreter.eval_str("(shout greeting)"),
+                   Ok(Value::String("HELLO".to_string())));
+        assert_eq!(interpreter.eval_value(Value::list(vec![Value::symbol("shout"),
+                                                           Value::symbol("greeting")]
+                                                      .into_iter())),
+                   Ok(Value::String("HELLO".to_string())));
+    }
+
+    #[test]
+    pub fn interpreter_errors() {
+        let interpreter = Interpreter::new();
+
+        assert!(matches!(interpreter.get("missing").unwrap_err().kind(),
+                         ErrorKind::UnboundSymbol(_)));
+        assert!(interpreter.call("missing", vec![]).is_err());
+        assert!(interpreter.call("if", vec![Value::Boolean(true)]).is_err());
+
+        let error = interpreter.eval_str_named("\n(undefined)", "lib.lisp").unwrap_err();
+        assert_eq!(error.location().map(|l| l.to_string()), Some("lib.lisp:2:1".to_string()));
+        assert_eq!(interpreter.eval_str("(+ 1 1)"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    pub fn convert_values() {
+        use std::collections::HashMap;
+
+        let value = |expr| read_and_eval(expr).unwrap();
+
+        assert_eq!(i64::from_value(value("42")), Ok(42));
+        assert_eq!(f64::from_value(value("1/4")), Ok(0.25));
+        assert_eq!(bool::from_value(value("true")), Ok(true));
+        assert_eq!(String::from_value(value("\"hi\"")), Ok("hi".to_string()));
+        assert_eq!(Vec::<i64>::from_value(value("'(1 2 3)")), Ok(vec![1, 2, 3]));
+        assert_eq!(Option::<i64>::from_value(value("nil")), Ok(None));
+        assert_eq!(<(i64, String)>::from_value(value("(list 1 \"a\")")),
+                   Ok((1, "a".to_string())));
+
+        let map = HashMap::<String, i64>::from_value(value("(list (cons 'a 1) (cons \"b\" 2))"))
+            .unwrap();
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get("b"), Some(&2));
+        assert_eq!(format!("{:?}", map.into_value()), "((\"a\" . 1) (\"b\" . 2))");
+
+        assert_eq!(vec![Some(1), None].into_value(), value("(list 1 nil)"));
+        assert_eq!(("a", 1.5, true).into_value(), value("(list \"a\" 1.5 true)"));
+
+        let type_error = |result: Result<_>| match result.unwrap_err().kind() {
+            ErrorKind::Type(msg) => msg.clone(),
+            kind => panic!("Expected a type error, got: {:?}", kind)
+        };
+
+        assert_eq!(type_error(i64::from_value(value("1.5")).map(|_| ())),
+                   "Expected integer, got: 1.5");
+        assert_eq!(type_error(Vec::<i64>::from_value(value("'(1 a)")).map(|_| ())),
+                   "Expected integer, got: a");
+        assert_eq!(type_error(<(i64, i64)>::from_value(value("'(1)")).map(|_| ())),
+                   "Expected list of length 2, got: (1)");
+    }
+
+    #[test]
+    pub fn interpreter_define_fn() {
+        fn repeat(n: i64, s: String) -> Result<String> {
+            Ok(s.repeat(n as usize))
+        }
+
+        let interpreter = Interpreter::new();
+
+        interpreter.define_fn("repeat", repeat);
+        interpreter.define_fn("longer?", |a: String, b: String| Ok(a.len() > b.len()));
+        interpreter.define_fn("sum", |values: Vec<f64>| Ok(values.iter().sum::<f64>()));
+        interpreter.define_fn("answer", || Ok(42));
+
+        assert_eq!(interpreter.eval_str("(repeat 3 \"ab\")"),
+                   Ok(Value::String("ababab".to_string())));
+        assert_eq!(interpreter.eval_str("(longer? \"abc\" \"ab\")"), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval_str("(sum '(1 2.5 1/2))"), Ok(Value::Float(4.0)));
+        assert_eq!(interpreter.eval_str("(answer)"), Ok(Value::Integer(42)));
+
+        assert!(match interpreter.eval_str("(repeat 3)").unwrap_err().kind() {
+            ErrorKind::Arity(msg) => msg == "Expected 2 arguments, got 1",
+            _ => false
+        });
+        assert!(match interpreter.eval_str("(sum)").unwrap_err().kind() {
+            ErrorKind::Arity(msg) => msg == "Expected 1 argument, got 0",
+            _ => false
+        });
+        assert!(match interpreter.eval_str("(repeat \"3\" \"ab\")").unwrap_err().kind() {
+            ErrorKind::Type(msg) => msg == "Expected integer, got: \"3\"",
+            _ => false
+        });
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Line(i64, i64),
+        Rect { width: i64, height: i64 }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        retries: Option<u32>,
+        tags: Vec<String>,
+        shapes: Vec<Shape>
+    }
+
+    #[test]
+    pub fn serialize_to_value() {
+        let config = Config {
+            name: "test".to_string(),
+            retries: None,
+            tags: vec!["a".to_string(), "b".to_string()],
+            shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Line(1, 2),
+                         Shape::Rect { width: 3, height: 4 }]
+        };
+
+        assert_eq!(format!("{:?}", to_value(&config).unwrap()),
+                   "((:name . \"test\") (:retries) (:tags \"a\" \"b\") \
+                    (:shapes :Empty (:Circle 1.5) (:Line 1 2) \
+                    (:Rect (:width . 3) (:height . 4))))");
+        assert_eq!(format!("{:?}", to_value(&(1, u64::MAX)).unwrap()),
+                   "(1 18446744073709551615)");
+    }
+
+    #[test]
+    pub fn deserialize_from_value() {
+        let interpreter = Interpreter::new();
+        let config = interpreter.eval_str("
+            (list (cons :name \"test\")
+                  (cons :retries 3)
+                  (cons :tags (list \"a\"))
+                  (list :shapes :Empty '(:Circle 2)
+                        (list :Rect (cons :width 1) (cons :height 2))))").unwrap();
+
+        assert_eq!(from_value::<Config>(config.clone()), Ok(Config {
+            name: "test".to_string(),
+            retries: Some(3),
+            tags: vec!["a".to_string()],
+            shapes: vec![Shape::Empty, Shape::Circle(2.0), Shape::Rect { width: 1, height: 2 }]
+        }));
+        assert_eq!(from_value::<Config>(to_value(&from_value::<Config>(config.clone()).unwrap())
+                                        .unwrap()),
+                   from_value::<Config>(config));
+
+        let map: ::std::collections::HashMap<String, Vec<i64>> =
+            from_value(interpreter.eval_str("(list (cons \"x\" '(1 2)))").unwrap()).unwrap();
+        assert_eq!(map["x"], vec![1, 2]);
+    }
+
+    #[test]
+    pub fn deserialize_errors() {
+        let interpreter = Interpreter::new();
+        let value = |expr| interpreter.eval_str(expr).unwrap();
+
+        assert!(from_value::<Config>(value("'(1 2)")).is_err());
+        assert!(from_value::<(i64, i64)>(value("'(1 2 3)")).is_err());
+        assert!(from_value::<Shape>(value("'(:Triangle 1)")).is_err());
+        assert!(from_value::<Shape>(value("'(:Line 1)")).is_err());
+        assert!(from_value::<u8>(value("300")).is_err());
+        assert!(matches!(from_value::<String>(value("1")).unwrap_err().kind(), ErrorKind::Type(_)));
+    }
+
+    #[test]
+    pub fn eval_limits() {
+        use std::time::{Duration, Instant};
+
+        let limit_exceeded = |result: Result<Value>| match result {
+            Err(e) => matches!(e.kind(), ErrorKind::LimitExceeded(_)),
+            Ok(_) => false
+        };
+
+        let interpreter = Interpreter::with_limits(Limits::default().max_steps(10000));
+        interpreter.eval_str("(defun forever () (forever))").unwrap();
+        assert!(limit_exceeded(interpreter.eval_str("(forever)")));
+        assert!(limit_exceeded(interpreter.call("forever", vec![])));
+        assert_eq!(interpreter.eval_str("(+ 1 2)"), Ok(Value::Integer(3)));
+
+        let interpreter = Interpreter::with_limits(Limits::default().max_depth(100));
+        interpreter.eval_str("(defun deeper (n) (+ 1 (deeper n)))").unwrap();
+        assert!(limit_exceeded(interpreter.eval_str("(deeper 0)")));
+        interpreter.eval_str("(defun count (n) (if (= n 0) 0 (+ 1 (count (- n 1)))))").unwrap();
+        assert_eq!(interpreter.eval_str("(count 20)"), Ok(Value::Integer(20)));
+
+        let interpreter = Interpreter::with_limits(Limits::default().max_cons_cells(100));
+        interpreter.eval_str("(defun grow (l) (grow (cons 1 l)))").unwrap();
+        assert!(limit_exceeded(interpreter.eval_str("(grow nil)")));
+        assert!(!limit_exceeded(interpreter.eval_str("(append '(1 2) (map - (list 1 2)))")));
+
+        let interpreter = Interpreter::with_limits(Limits::default()
+                                                   .timeout(Duration::from_millis(50)));
+        interpreter.eval_str("(defun forever () (forever))").unwrap();
+        let error = interpreter.eval_str("(forever)").unwrap_err();
+        assert_eq!(error.kind().to_string(), "Limit exceeded: evaluation took longer than 50ms");
+
+        let started = Instant::now();
+        assert!(limit_exceeded(interpreter.eval_str("(sleep 10)")));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    pub fn sandboxed_interpreter() {
+        let interpreter = Interpreter::sandboxed(Capability::PURE, Limits::default());
+        assert_eq!(interpreter.eval_str("(string-append \"ab\" \"cd\")"),
+                   Ok(Value::String("abcd".to_string())));
+        assert_eq!(interpreter.eval_str("(string-length \"h\u{e9}llo\")"), Ok(Value::Integer(5)));
+
+        for name in &["exit", "getenv", "println", "read-line", "read-file", "write-file",
+                      "current-time", "sleep"] {
+            assert!(matches!(interpreter.eval_str(name).unwrap_err().kind(),
+                             ErrorKind::UnboundSymbol(_)));
+        }
+
+        let interpreter = Interpreter::sandboxed(&[Capability::Math], Limits::default());
+        assert_eq!(interpreter.eval_str("(+ 1 2)"), Ok(Value::Integer(3)));
+        assert!(interpreter.eval_str("(if true 1 2)").is_err());
+
+        let interpreter = Interpreter::sandboxed(Capability::PURE,
+                                                 Limits::default().max_string_bytes(10));
+        assert!(match interpreter.eval_str("(string-append \"hello\" \"world\" \"!\")") {
+            Err(e) => e.kind() == &ErrorKind::LimitExceeded("more than 10 bytes of strings"
+                                                            .to_string()),
+            Ok(_) => false
+        });
+    }
+
+    #[test]
+    pub fn eval_io() {
+        let interpreter = Interpreter::new();
+        let eval = |expr: &str| interpreter.eval_str(expr);
+        let is_io_error = |expr: &str| match eval(expr) {
+            Err(e) => matches!(e.kind(), ErrorKind::Io(_)),
+            Ok(_) => false
+        };
+
+        let dir = std::env::temp_dir().join(format!("rasp-eval-io-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = format!("{:?}", dir.join("file.txt").to_str().unwrap());
+        let missing = format!("{:?}", dir.join("missing.txt").to_str().unwrap());
+
+        assert_eq!(eval(&format!("(file-exists? {})", path)), Ok(Value::Boolean(false)));
+        assert_eq!(eval(&format!("(write-file {} \"h\u{e9}llo\nworld\")", path)), Ok(Value::Nil));
+        assert_eq!(eval(&format!("(file-exists? {})", path)), Ok(Value::Boolean(true)));
+        assert_eq!(eval(&format!("(read-file {})", path)),
+                   Ok(Value::String("h\u{e9}llo\nworld".to_string())));
+        assert_eq!(eval(&format!("(write-file {} \"\")", path)), Ok(Value::Nil));
+        assert_eq!(eval(&format!("(read-file {})", path)), Ok(Value::String(String::new())));
+
+        assert!(is_io_error(&format!("(read-file {})", missing)));
+        assert!(is_io_error(&format!("(write-file {:?} \"x\")",
+                                     dir.join("missing").join("file.txt").to_str().unwrap())));
+        assert_eq!(eval(&format!("(file-exists? {})", missing)), Ok(Value::Boolean(false)));
+        assert!(eval("(read-file 1)").is_err());
+        assert!(eval(&format!("(write-file {} 1)", path)).is_err());
+        assert!(eval(&format!("(write-file {})", path)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        std::env::set_var("RASP_EVAL_IO_TEST", "value");
+        assert_eq!(eval("(getenv \"RASP_EVAL_IO_TEST\")"), Ok(Value::String("value".to_string())));
+        assert_eq!(eval("(getenv \"RASP_EVAL_IO_UNSET\")"), Ok(Value::Nil));
+        assert!(eval("(getenv 'PATH)").is_err());
+
+        assert_eq!(eval("(> (current-time) 1500000000)"), Ok(Value::Boolean(true)));
+        assert_eq!(eval("(<= (current-time) (current-time))"), Ok(Value::Boolean(true)));
+        assert!(eval("(current-time 1)").is_err());
+
+        assert_eq!(eval("(sleep 0)"), Ok(Value::Nil));
+        assert_eq!(eval("(sleep 0.001)"), Ok(Value::Nil));
+        assert!(eval("(sleep -1)").is_err());
+        assert!(matches!(eval("(sleep 1e20)").unwrap_err().kind(), ErrorKind::Type(_)));
+        assert!(eval("(sleep \"1\")").is_err());
+        assert!(eval("(sleep)").is_err());
+
+        assert!(eval("(read-line 1)").is_err());
+    }
+
+    #[test]
+    pub fn frozen_bindings() {
+        let interpreter = Interpreter::new();
+        interpreter.eval_str("(defun double (x) (* 2 x))").unwrap();
+        interpreter.freeze();
+
+        for expr in &["(defun + (a b) 0)", "(define if 1)", "(set double 1)", "(set! double nil)",
+                      "(defmacro double (x) x)"] {
+            assert!(matches!(interpreter.eval_str(expr).unwrap_err().kind(), ErrorKind::Frozen(_)));
+        }
+
+        assert_eq!(interpreter.eval_str("(double (+ 1 2))"), Ok(Value::Integer(6)));
+        assert_eq!(interpreter.eval_str("(let ((+ -)) (+ 3 1))"), Ok(Value::Integer(2)));
+        assert_eq!(interpreter.eval_str("(progn (define triple 3) (set! triple 4) triple)"),
+                   Ok(Value::Integer(4)));
+
+        interpreter.define("double", Value::Integer(2));
+        assert_eq!(interpreter.get("double"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    pub fn eval_interrupt() {
+        use std::thread;
+        use std::time::Duration;
+
+        let interpreter = Interpreter::new();
+        let interrupt = interpreter.interrupt_handle();
+        interpreter.eval_str("(defun forever (n) (forever (+ n 1)))").unwrap();
+
+        let handle = interrupt.clone();
+        let interrupter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.interrupt();
+        });
+
+        let error = interpreter.eval_str("(forever 0)").unwrap_err();
+        interrupter.join().unwrap();
+        assert_eq!(error.kind(), &ErrorKind::Interrupted);
+        assert!(!interrupt.is_interrupted());
+        assert_eq!(interpreter.eval_str("(+ 1 2)"), Ok(Value::Integer(3)));
+
+        let handle = interrupt.clone();
+        let interrupter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.interrupt();
+        });
+
+        let error = interpreter.eval_str("(sleep 1000)").unwrap_err();
+        interrupter.join().unwrap();
+        assert_eq!(error.kind(), &ErrorKind::Interrupted);
+
+        interrupt.interrupt();
+        interrupt.clear();
+        assert_eq!(interpreter.eval_str("(+ 1 2)"), Ok(Value::Integer(3)));
+    }
+
+    #[test]
+    pub fn drop_long_lists() {
+        drop(Value::list((0..300000).map(Value::Integer)));
+
+        let interpreter = Interpreter::new();
+        interpreter.eval_str("(defun build (n acc) (if (= n 0) acc (build (- n 1) (cons n acc))))")
+            .unwrap();
+        assert_eq!(interpreter.eval_str("(length (build 100000 nil))"), Ok(Value::Integer(100000)));
+    }
+
+    #[test]
+    pub fn eval_vectors() {
+        let interpreter = Interpreter::new();
+        let eval = |expr| interpreter.eval_str(expr).map(|value| value.to_string());
+
+        assert_eq!(eval("[1 (+ 1 1) \"three\" [4]]"), Ok("[1 2 three [4]]".to_string()));
+        assert_eq!(interpreter.eval_str("[1 \"a\"]").map(|value| format!("{:?}", value)),
+                   Ok("[1 \"a\"]".to_string()));
+        assert_eq!(eval("'[a b]"), Ok("[a b]".to_string()));
+        assert_eq!(eval("(let ((x 2)) `[1 ,x ,@(list 3 4)])"), Ok("[1 2 3 4]".to_string()));
+
+        interpreter.eval_str("(define v (vector 1 2 3))").unwrap();
+        assert_eq!(eval("(vector-ref v 1)"), Ok("2".to_string()));
+        assert_eq!(eval("(vector-length v)"), Ok("3".to_string()));
+        assert_eq!(eval("(progn (vector-set! v 0 'a) (vector-push! v 4) v)"),
+                   Ok("[a 2 3 4]".to_string()));
+        assert_eq!(eval("(vector->list v)"), Ok("(a 2 3 4)".to_string()));
+        assert_eq!(eval("(list->vector '(1 2))"), Ok("[1 2]".to_string()));
+        assert_eq!(eval("(= (vector 1 2) [1 2])"), Ok("true".to_string()));
+
+        // Each evaluation of a literal makes a new vector
+        interpreter.eval_str("(defun fresh () (let ((v [])) (vector-push! v 1) v))").unwrap();
+        assert_eq!(eval("(progn (fresh) (fresh))"), Ok("[1]".to_string()));
+
+        // Copies share the same vector
+        assert_eq!(eval("(let ((w v)) (vector-set! w 0 0) (vector-ref v 0))"),
+                   Ok("0".to_string()));
+
+        assert!(eval("(vector-ref v 4)").is_err());
+        assert!(eval("(vector-ref v -1)").is_err());
+        assert!(eval("(vector-set! '(1) 0 1)").is_err());
+        assert!(eval("(list->vector 1)").is_err());
+
+        let squares = "(list->vector (map (lambda (x) (* x x)) '(1 2 3)))";
+        assert_eq!(Vec::<i64>::from_value(interpreter.eval_str(squares).unwrap()),
+                   Ok(vec![1, 4, 9]));
+    }
+
+    #[test]
+    pub fn cyclic_values() {
+        let interpreter = Interpreter::new();
+        let eval = |expr| interpreter.eval_str(expr).map(|value| format!("{:?}", value));
+
+        interpreter.eval_str("(define v (vector 1)) (vector-push! v v)
+                              (define w (vector 1)) (vector-push! w w)
+                              (define h (hash-map :a 1)) (hash-set! h :self h)").unwrap();
+        assert_eq!(eval("v"), Ok("[1 #<cycle>]".to_string()));
+        assert_eq!(interpreter.eval_str("v").map(|value| value.to_string()),
+                   Ok("[1 #<cycle>]".to_string()));
+        assert_eq!(eval("(list v v)"), Ok("([1 #<cycle>] [1 #<cycle>])".to_string()));
+        assert_eq!(eval("h"), Ok("{:a 1 :self #<cycle>}".to_string()));
+        assert_eq!(eval("(= v v)"), Ok("true".to_string()));
+        assert_eq!(eval("(= v w)"), Ok("true".to_string()));
+        assert_eq!(eval("(= v (vector 1 w))"), Ok("true".to_string()));
+        assert_eq!(eval("(= v (vector 2 v))"), Ok("false".to_string()));
+        assert_eq!(eval("(= h h)"), Ok("true".to_string()));
+        assert_eq!(eval("(= h v)"), Ok("false".to_string()));
+
+        interpreter.eval_str("(define f (lambda () 1)) (define g (lambda () 1))").unwrap();
+        assert_eq!(eval("(list (= f f) (= f g))"), Ok("(true false)".to_string()));
+    }
+
+    #[test]
+    pub fn eval_hash_maps() {
+        use std::collections::HashMap;
+
+        let interpreter = Interpreter::new();
+        let eval = |expr| interpreter.eval_str(expr).map(|value| format!("{:?}", value));
+
+        assert_eq!(eval("(let ((c 'c))
+                           {:b 2 :a (+ 0 1) 10 \"ten\" #\\b 'b 9 nil \"s\" true c [3] false 0})"),
+                   Ok("{false 0 9 nil 10 \"ten\" #\\b b \"s\" true :a 1 :b 2 c [3]}".to_string()));
+        assert_eq!(eval("{}"), Ok("{}".to_string()));
+
+        // Literals are tables whose keys and values are evaluated in key
+        // order each time, into a new table
+        interpreter.eval_str("(define n 0)").unwrap();
+        assert_eq!(eval("{:b (progn (set! n (+ n 1)) n) :a (progn (set! n (+ n 1)) n)}"),
+                   Ok("{:a 1 :b 2}".to_string()));
+        assert_eq!(eval("{:b (progn (set! n (+ n 1)) n) :a (progn (set! n (+ n 1)) n)}"),
+                   Ok("{:a 3 :b 4}".to_string()));
+        assert_eq!(eval("(let ((k nil)) (list {:a (set! k 1) :b (set! k 2)} k))"),
+                   Ok("({:a nil :b nil} 2)".to_string()));
+        assert_eq!(eval("{:a 1 :a 2}"), Ok("{:a 2}".to_string()));
+        assert_eq!(eval("(let ((k :x) (y 'y)) {k 1 y 2})"), Ok("{:x 1 y 2}".to_string()));
+        assert_eq!(eval("(let ((k :x)) `{,k ,(+ 1 1) :y (a ,k)})"),
+                   Ok("{:x 2 :y (a :x)}".to_string()));
+        assert_eq!(eval("'{:a (+ 1 2)}"), Ok("{:a (+ 1 2)}".to_string()));
+        assert_eq!(eval("(hash-get '{:a (+ 1 2)} :a)"), Ok("(+ 1 2)".to_string()));
+        assert_eq!(eval("(let ((hash-map list)) {:a 1})"), Ok("{:a 1}".to_string()));
+        assert_eq!(eval("(defmacro table (x) `{:a ,x}) (macroexpand (table (+ 1 1)))"),
+                   Ok("{:a (+ 1 1)}".to_string()));
+        assert_eq!(eval("(table (+ 1 1))"), Ok("{:a 2}".to_string()));
+
+        interpreter.eval_str("(define m (hash-map :a 1 \"b\" 2))").unwrap();
+        assert_eq!(eval("(hash-get m :a)"), Ok("1".to_string()));
+        assert_eq!(eval("(hash-get m :missing)"), Ok("nil".to_string()));
+        assert_eq!(eval("(hash-get m :missing 0)"), Ok("0".to_string()));
+        assert_eq!(eval("(progn (hash-set! m 'c true) (hash-set! m :a 3) m)"),
+                   Ok("{\"b\" 2 :a 3 c true}".to_string()));
+        assert_eq!(eval("(hash-contains? m (string->symbol \"c\"))"), Ok("true".to_string()));
+        assert_eq!(eval("(hash-remove! m 'c)"), Ok("true".to_string()));
+        assert_eq!(eval("(hash-remove! m 'c)"), Ok("false".to_string()));
+        assert_eq!(eval("(hash-contains? m 'c)"), Ok("false".to_string()));
+        assert_eq!(eval("(hash-keys m)"), Ok("(\"b\" :a)".to_string()));
+        assert_eq!(eval("(hash-values m)"), Ok("(2 3)".to_string()));
+        assert_eq!(eval("(hash->alist m)"), Ok("((\"b\" . 2) (:a . 3))".to_string()));
+        assert_eq!(eval("(= {:a 1 :b 2} (hash-map :b 2 :a 1))"), Ok("true".to_string()));
+        assert_eq!(eval("(= {:a 1} {:a 2})"), Ok("false".to_string()));
+
+        assert!(eval("(hash-set! m 1.5 1)").is_err());
+        assert!(eval("(hash-set! m [1] 1)").is_err());
+        assert!(eval("(hash-set! m {} 1)").is_err());
+        assert!(eval("(hash-set! m '(1 2) 1)").is_err());
+        assert!(eval("(hash-set! m nil 1)").is_err());
+        assert!(eval("(hash-contains? m [1])").is_err());
+        assert!(eval("(hash-map :a)").is_err());
+        assert!(eval("(hash-get '((a . 1)) 'a)").is_err());
+        assert!(eval("{:a}").is_err());
+        assert!(eval("{[1] 2}").is_err());
+        assert!(eval("{(f) 2}").is_err());
+        assert!(eval("(let ((k [1])) {k 2})").is_err());
+
+        let map = HashMap::<String, i64>::from_value(interpreter.eval_str("{\"x\" 1 :y 2}")
+                                                     .unwrap());
+        assert_eq!(map.unwrap(), vec![("x".to_string(), 1), ("y".to_string(), 2)]
+                   .into_iter().collect());
+
+        let config: Config = from_value(interpreter.eval_str(
+            "{:name \"hash\" :retries nil :tags [] :shapes (list :Empty)}").unwrap()).unwrap();
+        assert_eq!(config.name, "hash");
+        assert_eq!(config.shapes, vec![Shape::Empty]);
+    }
+
+    #[test]
+    pub fn eval_strings() {
+        let interpreter = Interpreter::sandboxed(Capability::PURE, Limits::default());
+        let eval = |expr| interpreter.eval_str(expr).map(|value| format!("{:?}", value));
+
+        assert_eq!(eval(r"(list #\a #\newline #\u{1F600} #\u{7})"),
+                   Ok("(#\\a #\\newline #\\\u{1F600} #\\alarm)".to_string()));
+        assert_eq!(interpreter.eval_str(r"#\é").map(|value| value.to_string()),
+                   Ok("é".to_string()));
+        assert_eq!(eval(r#"(string-length "naïve 😀")"#), Ok("7".to_string()));
+        assert_eq!(eval(r#"(substring "naïve 😀" 2 5)"#), Ok(r#""ïve""#.to_string()));
+        assert_eq!(eval(r#"(substring "naïve 😀" 6)"#), Ok(r#""😀""#.to_string()));
+        assert_eq!(eval(r#"(substring "abc" 3)"#), Ok(r#""""#.to_string()));
+        assert_eq!(eval(r#"(string-append "a" "é" "")"#), Ok(r#""aé""#.to_string()));
+        assert_eq!(eval(r#"(string-split "a,b,,c" ",")"#), Ok(r#"("a" "b" "" "c")"#.to_string()));
+        assert_eq!(eval(r#"(string-split "  a  b ")"#), Ok(r#"("a" "b")"#.to_string()));
+        assert_eq!(eval(r#"(string-join '("a" "b" "c") ", ")"#), Ok(r#""a, b, c""#.to_string()));
+        assert_eq!(eval(r#"(string-join '("a" "b"))"#), Ok(r#""ab""#.to_string()));
+        assert_eq!(eval(r#"(string-trim "  hi there	")"#), Ok(r#""hi there""#.to_string()));
+        assert_eq!(eval(r#"(string-upcase "straße")"#), Ok(r#""STRASSE""#.to_string()));
+        assert_eq!(eval(r#"(string-downcase "ÀB")"#), Ok(r#""àb""#.to_string()));
+        assert_eq!(eval(r#"(string-index "héllo" "llo")"#), Ok("2".to_string()));
+        assert_eq!(eval(r#"(string-index "héllo" #\o)"#), Ok("4".to_string()));
+        assert_eq!(eval(r#"(string-index "héllo" "z")"#), Ok("nil".to_string()));
+        assert_eq!(eval(r#"(string-replace "a-b-c" "-" "+")"#), Ok(r#""a+b+c""#.to_string()));
+        assert_eq!(eval(r#"(string->list "aé")"#), Ok(r"(#\a #\é)".to_string()));
+        assert_eq!(eval(r#"(list (string->number "42") (string->number "-3/6")
+                                 (string->number "1.5") (string->number "x"))"#),
+                   Ok("(42 -1/2 1.5 nil)".to_string()));
+        assert_eq!(eval(r##"(list (string->number "#;1 2") (string->number " 2")
+                                  (string->number "2 3") (string->number "1/0"))"##),
+                   Ok("(nil nil nil nil)".to_string()));
+        assert_eq!(eval("(number->string 2.0)"), Ok(r#""2.0""#.to_string()));
+        assert_eq!(eval("(number->string 1/3)"), Ok(r#""1/3""#.to_string()));
+        assert_eq!(eval(r#"(string->symbol "abc")"#), Ok("abc".to_string()));
+        assert_eq!(eval("(symbol->string 'abc)"), Ok(r#""abc""#.to_string()));
+        assert_eq!(eval(r#"(= #\a (car (string->list "a")))"#), Ok("true".to_string()));
+        assert_eq!(eval(r"(hash-get {#\a 1} #\a)"), Ok("1".to_string()));
+
+        assert!(eval(r#"(substring "abc" 2 1)"#).is_err());
+        assert!(eval(r#"(substring "abc" 4)"#).is_err());
+        assert!(eval(r#"(substring "abc" -1)"#).is_err());
+        assert!(eval(r#"(string-split "abc" "")"#).is_err());
+        assert!(eval(r#"(string-join '("a" 1))"#).is_err());
+        assert!(eval(r#"(number->string "1")"#).is_err());
+        assert!(eval(r#"(symbol->string "a")"#).is_err());
+
+        assert_eq!(to_value(&'x'), Ok(Value::Char('x')));
+        assert_eq!(from_value::<char>(Value::Char('x')), Ok('x'));
+        assert_eq!(char::from_value(Value::Char('x')), Ok('x'));
+    }
+
+    #[test]
+    pub fn eval_keywords() {
+        let interpreter = Interpreter::new();
+        let eval = |expr| interpreter.eval_str(expr).map(|value| format!("{:?}", value));
+
+        assert_eq!(interpreter.eval_str(":a"), Ok(Value::keyword("a")));
+        assert_eq!(eval("(list :a ':b `:c)"), Ok("(:a :b :c)".to_string()));
+        assert_eq!(eval("(= :a ':a)"), Ok("true".to_string()));
+        assert_eq!(eval("(= :a 'a)"), Ok("false".to_string()));
+        assert_eq!(eval(r#"(= :a (string->symbol ":a"))"#), Ok("false".to_string()));
+        assert_eq!(eval("(hash-get (hash-map :a 1 'a 2) ':a)"), Ok("1".to_string()));
+
+        interpreter.eval_str("(defun greet (name :greeting (:punctuation \"!\"))
+                                (string-append greeting \", \" name punctuation))").unwrap();
+        assert_eq!(eval(r#"(greet "Ann" :greeting "Hi")"#), Ok(r#""Hi, Ann!""#.to_string()));
+        assert_eq!(eval(r#"(greet "Ann" :punctuation "?" :greeting "Hi")"#),
+                   Ok(r#""Hi, Ann?""#.to_string()));
+        assert!(eval(r#"(greet "Ann")"#).is_err());
+        assert!(eval(r#"(greet "Ann" :greeting)"#).is_err());
+        assert!(eval(r#"(greet "Ann" :greeting "Hi" :greeting "Hey")"#).is_err());
+
+        // A symbol whose name starts with a colon is an ordinary symbol
+        assert!(eval("|:a|").is_err());
+
+        // Keywords that don't name a keyword parameter are ordinary arguments
+        assert_eq!(eval("((lambda (x) x) :a)"), Ok(":a".to_string()));
+        assert_eq!(eval("(funcall (lambda (x) x) :a)"), Ok(":a".to_string()));
+        assert_eq!(eval("(map (lambda (k) k) '(:a :b))"), Ok("(:a :b)".to_string()));
+        assert_eq!(eval("(map (lambda (k) k) (hash-keys {:a 1 :b 2}))"),
+                   Ok("(:a :b)".to_string()));
+        assert_eq!(eval("(sort '(:b :a) (lambda (a b) (= a :a)))"), Ok("(:a :b)".to_string()));
+
+        assert_eq!(eval("(symbol->string :kw)"), Ok(r#""kw""#.to_string()));
+        assert!(eval("(symbol->string \"kw\")").is_err());
+    }
+
+    #[test]
+    pub fn eval_symbols() {
+        assert_eq!(Symbol::intern("foo"), Symbol::intern("foo"));
+        assert_ne!(Symbol::intern("foo"), Symbol::intern("bar"));
+        assert_eq!(Symbol::intern("foo").as_str(), "foo");
+        assert_eq!(Value::symbol("nil"), Value::Symbol(Symbol::NIL));
+
+        let interpreter = Interpreter::new();
+        let eval = |expr| interpreter.eval_str(expr).map(|value| format!("{:?}", value));
+
+        assert_eq!(eval("(= 'a (string->symbol \"a\"))"), Ok("true".to_string()));
+        assert_eq!(eval("(symbol->string 'abc)"), Ok("\"abc\"".to_string()));
+        assert_eq!(eval("(let ((x 1)) (let ((x 2)) x))"), Ok("2".to_string()));
+        assert!(eval("undefined-symbol").is_err());
+
+        // New symbols are never freed, so their names count as strings
+        let interpreter = Interpreter::with_limits(Limits::default().max_string_bytes(16));
+        assert!(interpreter.eval_str("(string->symbol \"list\")").is_ok());
+        assert!(match interpreter.eval_str("(string->symbol \"a-new-symbol-name-too-long\")") {
+            Err(e) => e.kind() == &ErrorKind::LimitExceeded("more than 16 bytes of strings"
+                                                            .to_string()),
+            Ok(_) => false
+        });
     }
 }
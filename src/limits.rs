@@ -0,0 +1,206 @@
+use std::cell::Cell;
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+use error::{ErrorKind, Result};
+use interrupt::InterruptHandle;
+
+/// Limits on the resources an evaluation may use, so that untrusted scripts
+/// can't run forever, recurse until the stack overflows or exhaust memory.
+/// Each limit is unlimited when `None`, as they all are by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// The most expressions that may be evaluated.
+    pub max_steps: Option<u64>,
+    /// How deeply evaluation may nest, which bounds non-tail recursion so
+    /// that it fails before the stack overflows.
+    pub max_depth: Option<usize>,
+    /// The most cons cells and vector elements that library functions may
+    /// allocate.
+    pub max_cons_cells: Option<u64>,
+    /// The most bytes of strings that library functions may allocate,
+    /// including the names of new symbols they create.
+    pub max_string_bytes: Option<u64>,
+    /// How long an evaluation may run for.
+    pub timeout: Option<Duration>
+}
+
+impl Limits {
+    pub fn max_steps(mut self, steps: u64) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub fn max_cons_cells(mut self, cells: u64) -> Self {
+        self.max_cons_cells = Some(cells);
+        self
+    }
+
+    pub fn max_string_bytes(mut self, bytes: u64) -> Self {
+        self.max_string_bytes = Some(bytes);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// The resources used so far against a set of limits, shared by every scope
+/// descended from the same root.
+#[derive(Debug, PartialEq)]
+pub struct Budget {
+    limits: Limits,
+    interrupt: InterruptHandle,
+    deadline: Cell<Option<Instant>>,
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    cons_cells: Cell<u64>,
+    string_bytes: Cell<u64>
+}
+
+fn exceeded<T>(msg: String) -> Result<T> {
+    Err(ErrorKind::LimitExceeded(msg).into())
+}
+
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        let budget = Budget {
+            limits,
+            interrupt: InterruptHandle::new(),
+            deadline: Cell::new(None),
+            steps: Cell::new(0),
+            depth: Cell::new(0),
+            cons_cells: Cell::new(0),
+            string_bytes: Cell::new(0)
+        };
+
+        budget.reset();
+        budget
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn interrupt_handle(&self) -> &InterruptHandle {
+        &self.interrupt
+    }
+
+    /// Forgets the resources used so far and restarts the clock, which the
+    /// `Interpreter` does at the start of each evaluation.
+    pub fn reset(&self) {
+        self.deadline.set(self.limits.timeout.map(|timeout| Instant::now() + timeout));
+        self.steps.set(0);
+        self.cons_cells.set(0);
+        self.string_bytes.set(0);
+    }
+
+    /// Counts the evaluation of one expression, failing if the evaluation
+    /// has been interrupted.
+    pub fn step(&self) -> Result<()> {
+        if self.interrupt.take() {
+            return Err(ErrorKind::Interrupted.into());
+        }
+
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+
+        if let Some(max) = self.limits.max_steps {
+            if steps > max {
+                return exceeded(format!("more than {} evaluation steps", max));
+            }
+        }
+
+        self.check_deadline(Instant::now())
+    }
+
+    fn check_deadline(&self, now: Instant) -> Result<()> {
+        match self.deadline.get() {
+            Some(deadline) if now > deadline => {
+                exceeded(format!("evaluation took longer than {:?}", self.limits.timeout.unwrap()))
+            },
+            _ => Ok(())
+        }
+    }
+
+    /// Sleeps for `duration`, waking to fail if the evaluation is interrupted
+    /// or runs out of time first.
+    pub fn sleep(&self, duration: Duration) -> Result<()> {
+        // How often to check for an interrupt
+        const INTERVAL: Duration = Duration::from_millis(10);
+
+        let end = Instant::now().checked_add(duration);
+
+        loop {
+            if self.interrupt.take() {
+                return Err(ErrorKind::Interrupted.into());
+            }
+
+            let now = Instant::now();
+            self.check_deadline(now)?;
+
+            let mut wait = match end {
+                Some(end) if now >= end => return Ok(()),
+                Some(end) => cmp::min(end - now, INTERVAL),
+                None => INTERVAL
+            };
+
+            if let Some(deadline) = self.deadline.get() {
+                // Just past the deadline, so that it's exceeded on waking
+                wait = cmp::min(wait, deadline - now + Duration::from_millis(1));
+            }
+
+            thread::sleep(wait);
+        }
+    }
+
+    /// Counts a nested evaluation until the returned guard is dropped.
+    pub(crate) fn enter(&self) -> Result<Depth<'_>> {
+        let depth = self.depth.get() + 1;
+
+        if let Some(max) = self.limits.max_depth {
+            if depth > max {
+                return exceeded(format!("evaluation nested more than {} deep", max));
+            }
+        }
+
+        self.depth.set(depth);
+        Ok(Depth(self))
+    }
+
+    pub fn allocate_cons_cells(&self, cells: usize) -> Result<()> {
+        let total = self.cons_cells.get() + cells as u64;
+        self.cons_cells.set(total);
+
+        match self.limits.max_cons_cells {
+            Some(max) if total > max => exceeded(format!("more than {} cons cells", max)),
+            _ => Ok(())
+        }
+    }
+
+    pub fn allocate_string_bytes(&self, bytes: usize) -> Result<()> {
+        let total = self.string_bytes.get() + bytes as u64;
+        self.string_bytes.set(total);
+
+        match self.limits.max_string_bytes {
+            Some(max) if total > max => exceeded(format!("more than {} bytes of strings", max)),
+            _ => Ok(())
+        }
+    }
+}
+
+/// A nested evaluation, counted against the maximum depth while it lives.
+pub(crate) struct Depth<'a>(&'a Budget);
+
+impl<'a> Drop for Depth<'a> {
+    fn drop(&mut self) {
+        self.0.depth.set(self.0.depth.get() - 1);
+    }
+}
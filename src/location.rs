@@ -0,0 +1,101 @@
+use std::fmt;
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use value::Value;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Location {
+    pub file: Option<Rc<str>>,
+    pub line: usize,
+    pub column: usize
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}:", file)?;
+        }
+
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Input text being read, along with the offsets of each line so that byte
+/// offsets recorded by the parser can be turned into lines and columns.
+pub struct Source<'a> {
+    pub text: &'a str,
+    file: Option<Rc<str>>,
+    line_starts: Vec<usize>
+}
+
+impl<'a> Source<'a> {
+    pub fn new(text: &'a str, file: Option<&str>) -> Self {
+        let line_starts = Some(0).into_iter()
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Source {
+            text,
+            file: file.map(|f| f.into()),
+            line_starts
+        }
+    }
+
+    pub fn locate(&self, offset: usize) -> Location {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1
+        };
+        let line_start = self.line_starts[line];
+
+        Location {
+            file: self.file.clone(),
+            line: line + 1,
+            column: self.text[line_start..offset].chars().count() + 1
+        }
+    }
+}
+
+// Cons cells are identified by the address of their car, which is shared by
+// every clone of the cell. Holding a weak reference keeps the allocation (but
+// not the value) around, so an address is never reused while it has an entry.
+struct Entry {
+    car: Weak<Value>,
+    location: Location
+}
+
+thread_local! {
+    static LOCATIONS: RefCell<HashMap<*const Value, Entry>> = RefCell::new(HashMap::new());
+    static PRUNE_AT: Cell<usize> = const { Cell::new(1024) };
+}
+
+fn key(car: &Rc<Value>) -> *const Value {
+    &**car as *const Value
+}
+
+pub fn record(car: &Rc<Value>, location: Location) {
+    LOCATIONS.with(|locations| {
+        let mut locations = locations.borrow_mut();
+
+        PRUNE_AT.with(|prune_at| {
+            if locations.len() >= prune_at.get() {
+                locations.retain(|_, entry| entry.car.upgrade().is_some());
+                prune_at.set((locations.len() * 2).max(1024));
+            }
+        });
+
+        locations.insert(key(car), Entry {
+            car: Rc::downgrade(car),
+            location
+        });
+    })
+}
+
+pub fn lookup(car: &Rc<Value>) -> Option<Location> {
+    LOCATIONS.with(|locations| {
+        locations.borrow()
+            .get(&key(car))
+            .map(|entry| entry.location.clone())
+    })
+}
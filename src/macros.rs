@@ -2,141 +2,189 @@ use std::rc::Rc;
 use std::ops::Deref;
 use std::collections::HashMap;
 use itertools::Itertools;
-use value::Value;
+use value::{Value, Tail};
 use scope::Scope;
 use function::{Function, Macro};
+use error::{ErrorKind, Result};
 
-fn progn(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Value {
-    args.into_iter()
-        .map(|e| e.eval(&scope))
-        .last()
-        .unwrap_or(Value::Nil)
+fn progn(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Result<Tail> {
+    let mut iter = args.into_iter();
+    let mut last = match iter.next() {
+        Some(expr) => expr,
+        None => return Ok(Tail::Return(Value::Nil))
+    };
+
+    for expr in iter {
+        last.eval(&scope)?;
+        last = expr;
+    }
+
+    Ok(Tail::Eval(last, scope))
 }
 
-fn if_macro(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Value {
+fn if_macro(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Result<Tail> {
     let mut iter = args.into_iter();
     let condition = iter.next()
-        .expect("Expected if condition")
-        .eval(&scope);
+        .ok_or_else(|| ErrorKind::Arity("Expected if condition".to_string()))?
+        .eval(&scope)?;
     let when_true = iter.next()
-        .expect("Expected statement to execute when true");
+        .ok_or_else(|| ErrorKind::Arity("Expected statement to execute when true".to_string()))?;
     let when_false = Value::progn(Value::list_rc(iter));
 
     if let Value::Boolean(condition) = condition {
         if condition {
-            when_true.eval(&scope)
+            Ok(Tail::Eval(when_true, scope))
         } else {
-            when_false.eval(&scope)
+            Ok(Tail::Eval(Rc::new(when_false), scope))
         }
     } else {
-        panic!("Expected boolean condition, got: {:?}", condition);
+        Err(ErrorKind::Type(format!("Expected boolean condition, got: {:?}", condition)).into())
+    }
+}
+
+fn set(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Result<Tail> {
+    if !args.len().is_multiple_of(2) {
+        return Err(ErrorKind::Arity("Uneven symbol and value pairs".to_string()).into());
     }
+
+    for (symbol, value) in args.into_iter().tuples() {
+        let symbol = symbol.as_symbol()
+            .ok_or_else(|| ErrorKind::Type(format!("Expected symbol, got: {:?}", symbol)))?;
+        scope.define_value(symbol, value.eval(&scope)?)?;
+    }
+
+    Ok(Tail::Return(Value::Nil))
 }
 
-fn set(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Value {
-    if args.len() % 2 != 0 {
-        panic!("Uneven symbol and value pairs");
+fn assign(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Result<Tail> {
+    if !args.len().is_multiple_of(2) {
+        return Err(ErrorKind::Arity("Uneven symbol and value pairs".to_string()).into());
     }
 
     for (symbol, value) in args.into_iter().tuples() {
-        scope.set_value(symbol.as_symbol().expect("Expected symbol").to_string(),
-                                value.eval(&scope));
+        let symbol = symbol.as_symbol()
+            .ok_or_else(|| ErrorKind::Type(format!("Expected symbol, got: {:?}", symbol)))?;
+        scope.assign_value(symbol, value.eval(&scope)?)?;
     }
 
-    Value::Nil
+    Ok(Tail::Return(Value::Nil))
 }
 
-fn let_block(args: Vec<Rc<Value>>, parent_scope: Rc<Scope>) -> Value {
+fn let_block(args: Vec<Rc<Value>>, parent_scope: Rc<Scope>) -> Result<Tail> {
     let scope = parent_scope.clone().push();
 
     let mut iter = args.into_iter();
     let vars = iter.next()
-        .expect("Expected variables list")
+        .ok_or_else(|| ErrorKind::Arity("Expected variables list".to_string()))?
         .iter_cons();
 
     for var in vars {
+        let var = var?;
+
         if let Value::Symbol(sym) = var.deref() {
-            scope.set_value(sym.to_string(), Value::Nil)
+            scope.set_value(*sym, Value::Nil)
         } else if let Some((symbol, value)) = var.as_symbol_value_pair() {
-            scope.clone().set_value(symbol.to_string(),
-                                    value.eval(&parent_scope));
+            scope.clone().set_value(symbol, value.eval(&parent_scope)?);
         } else {
-            panic!("Expected symbol or symbol and value pair");
+            return Err(ErrorKind::Type(
+                format!("Expected symbol or symbol and value pair, got: {:?}", var)).into());
         }
     }
 
-    iter.map(|e| e.eval(&scope))
-        .last()
-        .unwrap_or(Value::Nil)
+    progn(iter.collect(), scope)
 }
 
-pub fn defun(args: Vec<Rc<Value>>, parent_scope: Rc<Scope>) -> Value {
+pub fn defun(args: Vec<Rc<Value>>, parent_scope: Rc<Scope>) -> Result<Tail> {
     let mut iter = args.into_iter();
     let name = iter.next()
-        .and_then(|e| e.as_symbol().map(|s| s.to_string()))
-        .expect("Expected function name");
-    let params = iter.next().expect("Expected parameter definitions");
+        .and_then(|e| e.as_symbol())
+        .ok_or_else(|| ErrorKind::Type("Expected function name".to_string()))?;
+    let params = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected parameter definitions".to_string()))?;
 
-    let function = Function::define(name.clone(), &params,
+    let function = Function::define(Some(name.to_string()), &params,
                                     Rc::new(Value::list_rc(iter)),
-                                    parent_scope.clone());
+                                    parent_scope.clone())?;
 
-    parent_scope.set_value(name, Value::Function(Rc::new(function)));
+    parent_scope.define_value(name, Value::Function(Rc::new(function)))?;
 
-    Value::Nil
+    Ok(Tail::Return(Value::Nil))
 }
 
-pub fn defmacro(args: Vec<Rc<Value>>, parent_scope: Rc<Scope>) -> Value {
+pub fn lambda(args: Vec<Rc<Value>>, parent_scope: Rc<Scope>) -> Result<Tail> {
+    let mut iter = args.into_iter();
+    let params = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected parameter definitions".to_string()))?;
+
+    let function = Function::define(None, &params,
+                                    Rc::new(Value::list_rc(iter)),
+                                    parent_scope)?;
+
+    Ok(Tail::Return(Value::Function(Rc::new(function))))
+}
+
+pub fn defmacro(args: Vec<Rc<Value>>, parent_scope: Rc<Scope>) -> Result<Tail> {
     let mut iter = args.into_iter();
     let name = iter.next()
-        .and_then(|e| e.as_symbol().map(|s| s.to_string()))
-        .expect("Expected macro name");
-    let params = iter.next().expect("Expected parameter definitions");
+        .and_then(|e| e.as_symbol())
+        .ok_or_else(|| ErrorKind::Type("Expected macro name".to_string()))?;
+    let params = iter.next()
+        .ok_or_else(|| ErrorKind::Arity("Expected parameter definitions".to_string()))?;
 
-    let func = Macro::define(name.clone(), &params,
+    let func = Macro::define(name.to_string(), &params,
                               Rc::new(Value::list_rc(iter)),
-                              parent_scope.clone());
+                              parent_scope.clone())?;
 
-    parent_scope.set_value(name, Value::Macro(Rc::new(func)));
+    parent_scope.define_value(name, Value::Macro(Rc::new(func)))?;
 
-    Value::Nil
+    Ok(Tail::Return(Value::Nil))
 }
 
-pub fn quote(args: Vec<Rc<Value>>, _scope: Rc<Scope>) -> Value {
-    assert!(args.len() == 1, "Expected only one argument");
+pub fn quote(args: Vec<Rc<Value>>, _scope: Rc<Scope>) -> Result<Tail> {
+    if args.len() != 1 {
+        return Err(ErrorKind::Arity("Expected only one argument".to_string()).into());
+    }
 
-    args.into_iter().next().unwrap().deref().clone()
+    Ok(Tail::Return(args.into_iter().next().unwrap().deref().clone()))
 }
 
-pub fn macroexpand(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Value {
-    assert!(args.len() == 1, "Expected only one argument");
+pub fn macroexpand(args: Vec<Rc<Value>>, scope: Rc<Scope>) -> Result<Tail> {
+    if args.len() != 1 {
+        return Err(ErrorKind::Arity("Expected only one argument".to_string()).into());
+    }
 
     let arg = &args[0];
 
     if let Value::Cons(name, args) = arg.deref() {
         if let Value::Symbol(name) = name.deref() {
-            if let Value::Macro(func) = scope.get_value(name) {
-                let args = args.clone().iter_cons()
-                    .map(|v| v.deref().clone())
-                    .collect();
+            if let Ok(Value::Macro(func)) = scope.get_value(*name) {
+                let args = args.as_rc().clone().iter_cons()
+                    .map(|v| Ok(v?.deref().clone()))
+                    .collect::<Result<_>>()?;
 
-                return func.call(args);
+                return Ok(Tail::Return(func.call(args)?));
             }
         }
     }
 
-    arg.deref().clone()
+    Ok(Tail::Return(arg.deref().clone()))
 }
 
 pub fn register(scope: &mut HashMap<String, Value>) {
     scope.insert("set".to_string(),
                  Value::NativeMacro("set".to_string(), set));
+    scope.insert("define".to_string(),
+                 Value::NativeMacro("define".to_string(), set));
+    scope.insert("set!".to_string(),
+                 Value::NativeMacro("set!".to_string(), assign));
     scope.insert("let".to_string(),
                  Value::NativeMacro("let".to_string(), let_block));
     scope.insert("if".to_string(),
                  Value::NativeMacro("if".to_string(), if_macro));
     scope.insert("defun".to_string(),
                  Value::NativeMacro("defun".to_string(), defun));
+    scope.insert("lambda".to_string(),
+                 Value::NativeMacro("lambda".to_string(), lambda));
     scope.insert("defmacro".to_string(),
                  Value::NativeMacro("defmacro".to_string(), defmacro));
     scope.insert("progn".to_string(),
@@ -1,27 +1,327 @@
 extern crate rasp;
+extern crate rustyline;
+extern crate ctrlc;
 
 use std::env;
 use std::io;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 use std::fs::File;
-use rasp::read_and_eval;
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use rustyline::Editor;
+use rustyline::error::ReadlineError;
+use rasp::{Error, ErrorKind, Interpreter, IntoValue, Value};
+
+const USAGE: &str = "\
+Usage: rasp [OPTIONS] [FILE...] [-- ARG...]
+
+Evaluates each FILE in turn, sharing definitions between them, where - means
+standard input. With no files or expressions, starts a REPL if input is a
+terminal, and otherwise evaluates standard input.
+
+Options:
+  -e EXPR    evaluate EXPR, in order with any files
+  -i         start a REPL after evaluating files and expressions
+  --check    only check that files and expressions can be read
+  -h, --help show this message
+  --         pass the remaining arguments to scripts as the list *args*";
+
+const PROMPT: &str = "rasp> ";
+const CONTINUATION_PROMPT: &str = "  ... ";
+
+const HELP: &str = "\
+Enter an expression to evaluate it, or one of these commands:
+  ,load FILE    evaluate the contents of a file
+  ,expand FORM  show the result of expanding a macro call
+  ,help         show this message
+  ,quit         exit (as does Ctrl-D)";
+
+enum Source {
+    File(String),
+    Expr(String)
+}
+
+struct Options {
+    sources: Vec<Source>,
+    args: Vec<String>,
+    check: bool,
+    interactive: bool,
+    help: bool
+}
+
+fn parse_args(mut args: impl Iterator<Item=String>) -> Result<Options, String> {
+    let mut options = Options {
+        sources: Vec::new(),
+        args: Vec::new(),
+        check: false,
+        interactive: false,
+        help: false
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" => {
+                let expr = args.next().ok_or("Expected an expression after -e")?;
+                options.sources.push(Source::Expr(expr));
+            },
+            "-i" => options.interactive = true,
+            "--check" => options.check = true,
+            "-h" | "--help" => options.help = true,
+            "--" => options.args.extend(&mut args),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("Unknown option: {}", flag));
+            },
+            _ => options.sources.push(Source::File(arg))
+        }
+    }
+
+    Ok(options)
+}
 
 fn main() {
-    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", USAGE);
+        return;
+    }
 
-    if args.len() >= 1 {
-        for arg in args {
-            let mut file = File::open(arg)
-                .expect("Unable to find file");
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)
-                .expect("Unable to read file");
-            read_and_eval(&contents);
+    process::exit(run(options));
+}
+
+/// Runs everything the options ask for, returning the exit status.
+fn run(options: Options) -> i32 {
+    let interpreter = Interpreter::new();
+    interpreter.define("*args*", options.args.into_value());
+
+    if options.sources.is_empty() && !options.interactive {
+        if io::stdin().is_terminal() && !options.check {
+            return repl(&interpreter);
         }
-    } else {
+
         let mut buffer = String::new();
         io::stdin().read_to_string(&mut buffer)
             .expect("Unable to read input from stdin");
-        read_and_eval(&buffer);
+        return eval_source(&interpreter, &buffer, "<stdin>", options.check);
     }
+
+    for source in options.sources {
+        let status = match source {
+            Source::File(path) => {
+                match read_file(&path) {
+                    Ok(contents) => {
+                        eval_source(&interpreter, skip_shebang(&contents), &path, options.check)
+                    },
+                    Err(e) => {
+                        eprintln!("Unable to read {}: {}", path, e);
+                        1
+                    }
+                }
+            },
+            Source::Expr(expr) => eval_source(&interpreter, &expr, "<expr>", options.check)
+        };
+
+        if status != 0 {
+            return status;
+        }
+    }
+
+    if options.interactive && !options.check {
+        repl(&interpreter)
+    } else {
+        0
+    }
+}
+
+/// Reads a file, or standard input if the path is `-`.
+fn read_file(path: &str) -> io::Result<String> {
+    let mut contents = String::new();
+
+    if path == "-" {
+        io::stdin().read_to_string(&mut contents)?;
+    } else {
+        File::open(path)?.read_to_string(&mut contents)?;
+    }
+
+    Ok(contents)
+}
+
+/// Blanks out a `#!` line so scripts can be run directly, keeping the line
+/// break so that locations in errors still match the file.
+fn skip_shebang(contents: &str) -> &str {
+    if contents.starts_with("#!") {
+        &contents[contents.find('\n').unwrap_or(contents.len())..]
+    } else {
+        contents
+    }
+}
+
+/// Evaluates (or with `check`, just reads) a source, returning the exit
+/// status.
+fn eval_source(interpreter: &Interpreter, contents: &str, name: &str, check: bool) -> i32 {
+    let result = if check {
+        rasp::read_named(contents, name).map(|_| Value::Nil)
+    } else {
+        interpreter.eval_str_named(contents, name)
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => exit_status(&e)
+    }
+}
+
+/// The exit status for an error, which is reported unless it came from a
+/// call to `exit`.
+fn exit_status(error: &Error) -> i32 {
+    match error.kind() {
+        ErrorKind::Exit(status) => *status,
+        _ => {
+            eprintln!("Error: {}", error);
+            1
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".rasp_history"))
+}
+
+/// Runs a REPL until the input ends or `exit` is called, returning the exit
+/// status.
+fn repl(interpreter: &Interpreter) -> i32 {
+    let mut editor = Editor::<()>::new();
+    let history = history_path();
+    let interrupt = interpreter.interrupt_handle();
+
+    // Ctrl-C while reading input is handled by the editor, but while an
+    // expression is being evaluated it arrives as a signal, which stops the
+    // evaluation and returns to the prompt.
+    let handler_interrupt = interrupt.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_interrupt.interrupt()) {
+        eprintln!("Unable to handle Ctrl-C: {}", e);
+    }
+
+    if let Some(ref path) = history {
+        // There's no history the first time the REPL is run
+        let _ = editor.load_history(path);
+    }
+
+    let mut input = String::new();
+    let mut status = 0;
+
+    loop {
+        let prompt = if input.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+
+        match editor.readline(prompt) {
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+
+                if rasp::is_incomplete(&input) {
+                    continue;
+                }
+
+                let entry = mem::take(&mut input);
+                let entry = entry.trim();
+
+                if entry.is_empty() {
+                    continue;
+                }
+
+                editor.add_history_entry(entry);
+                interrupt.clear();
+
+                if let Some(exit_status) = run_entry(interpreter, entry) {
+                    status = exit_status;
+                    break;
+                }
+            },
+            // Ctrl-C abandons the expression being entered
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                status = 1;
+                break;
+            }
+        }
+    }
+
+    if let Some(ref path) = history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("Unable to save history: {}", e);
+        }
+    }
+
+    status
+}
+
+/// Evaluates an expression or runs a command, returning an exit status if
+/// the REPL should exit.
+fn run_entry(interpreter: &Interpreter, entry: &str) -> Option<i32> {
+    if !entry.starts_with(',') {
+        return print_result(interpreter.eval_str(entry));
+    }
+
+    let (command, arg) = match entry.find(char::is_whitespace) {
+        Some(i) => (&entry[..i], entry[i..].trim()),
+        None => (entry, "")
+    };
+
+    match command {
+        ",quit" | ",q" => Some(0),
+        ",load" => load(interpreter, arg),
+        ",expand" => expand(interpreter, arg),
+        ",help" => {
+            println!("{}", HELP);
+            None
+        },
+        _ => {
+            eprintln!("Unknown command: {} (try ,help)", command);
+            None
+        }
+    }
+}
+
+/// Prints a result, returning an exit status if it came from `exit`.
+fn print_result(result: rasp::Result<Value>) -> Option<i32> {
+    match result {
+        Ok(value) => println!("{:?}", value),
+        Err(e) => {
+            if let ErrorKind::Exit(status) = e.kind() {
+                return Some(*status);
+            }
+
+            eprintln!("Error: {}", e);
+        }
+    }
+
+    None
+}
+
+fn load(interpreter: &Interpreter, path: &str) -> Option<i32> {
+    match read_file(path) {
+        Ok(contents) => print_result(interpreter.eval_str_named(skip_shebang(&contents), path)),
+        Err(e) => {
+            eprintln!("Unable to read {}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn expand(interpreter: &Interpreter, form: &str) -> Option<i32> {
+    let result = rasp::read(form).and_then(|form| {
+        interpreter.eval_value(Value::list(vec![Value::symbol("macroexpand"), form].into_iter()))
+    });
+
+    print_result(result)
 }
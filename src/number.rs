@@ -0,0 +1,424 @@
+use std::cmp::Ordering;
+use std::f64;
+use std::ops::{Add, Sub, Mul, Div, Rem};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, Pow, Signed, ToPrimitive, Zero};
+use num_integer::Integer;
+use value::Value;
+use error::{ErrorKind, Result};
+
+// The most bits `expt` may give the numerator or denominator of its result,
+// so that a single call can't use up all the memory or time available.
+const MAX_EXPT_BITS: u64 = 1 << 20;
+
+fn check_expt_size(base: &BigInt, power: u32) -> Result<()> {
+    // A base with n bits is at least 2^(n - 1)
+    let bits = (base.bits() as u64).saturating_sub(1).saturating_mul(u64::from(power));
+
+    if bits > MAX_EXPT_BITS {
+        Err(ErrorKind::Arithmetic(format!("Result too large: {} ^ {}", base, power)).into())
+    } else {
+        Ok(())
+    }
+}
+
+impl From<BigInt> for Value {
+    /// Big integers are demoted to `Value::Integer` when they fit in one.
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(n) => Value::Integer(n),
+            None => Value::BigInt(n)
+        }
+    }
+}
+
+impl From<BigRational> for Value {
+    /// Ratios are demoted to integers when they're whole numbers.
+    fn from(n: BigRational) -> Self {
+        if n.is_integer() {
+            Value::from(n.to_integer())
+        } else {
+            Value::Ratio(n)
+        }
+    }
+}
+
+// Two numbers converted to the simplest representation that can hold both,
+// going from integers to big integers, then ratios, and finally floats.
+enum Operands {
+    Integer(i64, i64),
+    BigInt(BigInt, BigInt),
+    Ratio(BigRational, BigRational),
+    Float(f64, f64)
+}
+
+fn float_to_integer(n: f64) -> Result<Value> {
+    BigInt::from_f64(n)
+        .map(Value::from)
+        .ok_or_else(|| {
+            ErrorKind::Arithmetic(format!("Unable to convert {:?} to an integer", n)).into()
+        })
+}
+
+impl Value {
+    pub fn is_number(&self) -> bool {
+        self.rank().is_some()
+    }
+
+    /// Whether the value is an integer or ratio, rather than a float.
+    pub fn is_exact(&self) -> bool {
+        match self.rank() {
+            Some(rank) => rank < 3,
+            None => false
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Value::Integer(n) => *n == 0,
+            Value::BigInt(n) => n.is_zero(),
+            Value::Ratio(n) => n.is_zero(),
+            Value::Float(n) => *n == 0.0,
+            _ => false
+        }
+    }
+
+    fn rank(&self) -> Option<u8> {
+        match self {
+            Value::Integer(_) => Some(0),
+            Value::BigInt(_) => Some(1),
+            Value::Ratio(_) => Some(2),
+            Value::Float(_) => Some(3),
+            _ => None
+        }
+    }
+
+    fn into_bigint(self) -> BigInt {
+        match self {
+            Value::Integer(n) => BigInt::from(n),
+            Value::BigInt(n) => n,
+            _ => unreachable!("Expected an integer")
+        }
+    }
+
+    fn into_ratio(self) -> BigRational {
+        match self {
+            Value::Ratio(n) => n,
+            n => BigRational::from_integer(n.into_bigint())
+        }
+    }
+
+    /// The value of any number as a float, which may lose precision.
+    pub fn as_float(&self) -> Option<f64> {
+        if self.is_number() {
+            Some(self.clone().into_float())
+        } else {
+            None
+        }
+    }
+
+    fn into_float(self) -> f64 {
+        match self {
+            Value::Integer(n) => n as f64,
+            Value::BigInt(n) => n.to_f64().unwrap_or(f64::NAN),
+            Value::Ratio(n) => {
+                match (n.numer().to_f64(), n.denom().to_f64()) {
+                    (Some(numer), Some(denom)) => numer / denom,
+                    _ => f64::NAN
+                }
+            },
+            Value::Float(n) => n,
+            _ => unreachable!("Expected a number")
+        }
+    }
+
+    fn operands(self, other: Value) -> Operands {
+        match self.rank().max(other.rank()) {
+            Some(0) => Operands::Integer(self.into_bigint().to_i64().unwrap(),
+                                         other.into_bigint().to_i64().unwrap()),
+            Some(1) => Operands::BigInt(self.into_bigint(), other.into_bigint()),
+            Some(2) => Operands::Ratio(self.into_ratio(), other.into_ratio()),
+            _ => Operands::Float(self.into_float(), other.into_float())
+        }
+    }
+
+    /// Applies a binary numeric operation. Integer operations fall back to
+    /// big integers when `integer` returns `None` because the result overflows.
+    fn arithmetic(self, other: Value, verb: &str,
+                  integer: fn(i64, i64) -> Option<i64>,
+                  big: fn(BigInt, BigInt) -> Value,
+                  ratio: fn(BigRational, BigRational) -> BigRational,
+                  float: fn(f64, f64) -> f64) -> Result<Value> {
+        if !self.is_number() || !other.is_number() {
+            return Err(ErrorKind::Type(format!("Unable to {} {:?} and {:?}",
+                                               verb, self, other)).into());
+        }
+
+        Ok(match self.operands(other) {
+            Operands::Integer(a, b) => {
+                match integer(a, b) {
+                    Some(n) => Value::Integer(n),
+                    None => big(BigInt::from(a), BigInt::from(b))
+                }
+            },
+            Operands::BigInt(a, b) => big(a, b),
+            Operands::Ratio(a, b) => Value::from(ratio(a, b)),
+            Operands::Float(a, b) => Value::Float(float(a, b))
+        })
+    }
+
+    fn check_divisor(&self, divisor: &Value) -> Result<()> {
+        if self.is_number() && divisor.is_zero() {
+            Err(ErrorKind::Arithmetic(format!("Division by zero: {:?} / {:?}", self, divisor))
+                .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn expect_number(self) -> Result<Value> {
+        if self.is_number() {
+            Ok(self)
+        } else {
+            Err(ErrorKind::Type(format!("Expected number, got: {:?}", self)).into())
+        }
+    }
+
+    /// The remainder of floored division, which has the sign of the divisor.
+    pub fn modulo(self, other: Value) -> Result<Value> {
+        self.check_divisor(&other)?;
+        self.arithmetic(other, "take the modulus of",
+                        |a, b| {
+                            let rem = a.checked_rem(b)?;
+                            if rem != 0 && (rem < 0) != (b < 0) {
+                                rem.checked_add(b)
+                            } else {
+                                Some(rem)
+                            }
+                        },
+                        |a, b| Value::from(a.mod_floor(&b)),
+                        |a, b| ((a % b.clone()) + b.clone()) % b,
+                        |a, b| ((a % b) + b) % b)
+    }
+
+    pub fn compare(&self, other: &Value) -> Result<Ordering> {
+        let ordering = if self.is_number() && other.is_number() {
+            match self.clone().operands(other.clone()) {
+                Operands::Integer(a, b) => Some(a.cmp(&b)),
+                Operands::BigInt(a, b) => Some(a.cmp(&b)),
+                Operands::Ratio(a, b) => Some(a.cmp(&b)),
+                Operands::Float(a, b) => a.partial_cmp(&b)
+            }
+        } else {
+            None
+        };
+
+        ordering.ok_or_else(|| {
+            ErrorKind::Type(format!("Unable to compare {:?} and {:?}", self, other)).into()
+        })
+    }
+
+    pub fn abs(self) -> Result<Value> {
+        match self {
+            Value::Integer(n) => {
+                Ok(n.checked_abs()
+                   .map(Value::Integer)
+                   .unwrap_or_else(|| Value::from(BigInt::from(n).abs())))
+            },
+            Value::BigInt(n) => Ok(Value::from(n.abs())),
+            Value::Ratio(n) => Ok(Value::from(n.abs())),
+            Value::Float(n) => Ok(Value::Float(n.abs())),
+            value => value.expect_number()
+        }
+    }
+
+    fn round_with(self, ratio: fn(&BigRational) -> BigRational,
+                  float: fn(f64) -> f64) -> Result<Value> {
+        match self {
+            Value::Ratio(n) => Ok(Value::from(ratio(&n))),
+            Value::Float(n) => float_to_integer(float(n)),
+            value => value.expect_number()
+        }
+    }
+
+    pub fn floor(self) -> Result<Value> {
+        self.round_with(BigRational::floor, f64::floor)
+    }
+
+    pub fn ceil(self) -> Result<Value> {
+        self.round_with(BigRational::ceil, f64::ceil)
+    }
+
+    /// Rounds to the nearest integer, with halves rounded away from zero.
+    pub fn round(self) -> Result<Value> {
+        self.round_with(BigRational::round, f64::round)
+    }
+
+    /// The square root, which is exact if it can be.
+    pub fn sqrt(self) -> Result<Value> {
+        let value = self.expect_number()?;
+
+        if value.compare(&Value::Integer(0))? == Ordering::Less {
+            return Err(ErrorKind::Arithmetic(format!("Unable to take the square root of {:?}",
+                                                     value)).into());
+        }
+
+        let exact_root = |n: &BigInt| {
+            let root = n.sqrt();
+
+            if &root * &root == *n {
+                Some(root)
+            } else {
+                None
+            }
+        };
+
+        let exact = match value {
+            Value::Integer(_) | Value::BigInt(_) => {
+                exact_root(&value.clone().into_bigint()).map(Value::from)
+            },
+            Value::Ratio(ref n) => {
+                match (exact_root(n.numer()), exact_root(n.denom())) {
+                    (Some(numer), Some(denom)) => {
+                        Some(Value::from(BigRational::new(numer, denom)))
+                    },
+                    _ => None
+                }
+            },
+            _ => None
+        };
+
+        Ok(exact.unwrap_or_else(|| Value::Float(value.into_float().sqrt())))
+    }
+
+    /// Raises to a power, which is exact for exact bases and integer powers.
+    pub fn expt(self, power: Value) -> Result<Value> {
+        match (self, power) {
+            (Value::Integer(base), Value::Integer(power))
+                if power >= 0 && power <= u32::MAX as i64 => {
+                match base.checked_pow(power as u32) {
+                    Some(n) => Ok(Value::Integer(n)),
+                    None => {
+                        let base = BigInt::from(base);
+                        check_expt_size(&base, power as u32)?;
+                        Ok(Value::from(base.pow(power as u32)))
+                    }
+                }
+            },
+            (ref base, ref power) if base.is_exact() && power.is_exact() &&
+                                     !power.is_integer() => {
+                Ok(Value::Float(base.clone().into_float().powf(power.clone().into_float())))
+            },
+            (base, power) if base.is_exact() && power.is_exact() => {
+                let power = match power {
+                    Value::Integer(n) if n.abs() <= u32::MAX as i64 => n,
+                    power => {
+                        return Err(ErrorKind::Arithmetic(format!("Exponent too large: {:?}",
+                                                                 power)).into())
+                    }
+                };
+
+                if power < 0 && base.is_zero() {
+                    return Err(ErrorKind::Arithmetic(format!("Division by zero: {:?} ^ {}",
+                                                             base, power)).into());
+                }
+
+                let base = base.into_ratio();
+                let exponent = power.unsigned_abs() as u32;
+                check_expt_size(base.numer(), exponent)?;
+                check_expt_size(base.denom(), exponent)?;
+                let numer = base.numer().pow(exponent);
+                let denom = base.denom().pow(exponent);
+
+                if power < 0 {
+                    Ok(Value::from(BigRational::new(denom, numer)))
+                } else {
+                    Ok(Value::from(BigRational::new(numer, denom)))
+                }
+            },
+            (base, power) => {
+                if base.is_number() && power.is_number() {
+                    Ok(Value::Float(base.into_float().powf(power.into_float())))
+                } else {
+                    Err(ErrorKind::Type(format!("Unable to raise {:?} to the power {:?}",
+                                                base, power)).into())
+                }
+            }
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(self, Value::Integer(_) | Value::BigInt(_))
+    }
+}
+
+impl Add for Value {
+    type Output = Result<Value>;
+
+    fn add(self, other: Value) -> Result<Value> {
+        self.arithmetic(other, "add",
+                        |a, b| a.checked_add(b),
+                        |a, b| Value::from(a + b),
+                        |a, b| a + b,
+                        |a, b| a + b)
+    }
+}
+
+impl Sub for Value {
+    type Output = Result<Value>;
+
+    fn sub(self, other: Value) -> Result<Value> {
+        self.arithmetic(other, "subtract",
+                        |a, b| a.checked_sub(b),
+                        |a, b| Value::from(a - b),
+                        |a, b| a - b,
+                        |a, b| a - b)
+    }
+}
+
+impl Mul for Value {
+    type Output = Result<Value>;
+
+    fn mul(self, other: Value) -> Result<Value> {
+        self.arithmetic(other, "multiply",
+                        |a, b| a.checked_mul(b),
+                        |a, b| Value::from(a * b),
+                        |a, b| a * b,
+                        |a, b| a * b)
+    }
+}
+
+impl Div for Value {
+    type Output = Result<Value>;
+
+    /// Dividing integers gives an exact ratio when they don't divide evenly.
+    fn div(self, other: Value) -> Result<Value> {
+        self.check_divisor(&other)?;
+        self.arithmetic(other, "divide",
+                        |a, b| {
+                            if a.checked_rem(b)? == 0 {
+                                a.checked_div(b)
+                            } else {
+                                None
+                            }
+                        },
+                        |a, b| Value::from(BigRational::new(a, b)),
+                        |a, b| a / b,
+                        |a, b| a / b)
+    }
+}
+
+impl Rem for Value {
+    type Output = Result<Value>;
+
+    /// The remainder of truncated division, which has the sign of the dividend.
+    fn rem(self, other: Value) -> Result<Value> {
+        self.check_divisor(&other)?;
+        self.arithmetic(other, "take the remainder of",
+                        |a, b| a.checked_rem(b),
+                        |a, b| Value::from(a % b),
+                        |a, b| a % b,
+                        |a, b| a % b)
+    }
+}
@@ -1,84 +1,118 @@
 use std::rc::Rc;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use itertools::Itertools;
 use value::Value;
 use scope::Scope;
+use symbol::Symbol;
+use error::{ErrorKind, Result};
 
 #[derive(PartialEq)]
 pub struct Params {
-    required_params: Vec<String>,
-    optional_params: Vec<(String, Rc<Value>)>,
-    keyword_params: Vec<(String, Option<Rc<Value>>)>,
-    rest_param: Option<String>,
+    required_params: Vec<Symbol>,
+    optional_params: Vec<(Symbol, Rc<Value>)>,
+    keyword_params: Vec<(Symbol, Option<Rc<Value>>)>,
+    rest_param: Option<Symbol>,
 }
 
 impl Params {
-    pub fn parse(sexpr: &Value) -> Params {
-        let params = sexpr.as_list().expect("Expected parameter list");
+    pub fn parse(sexpr: &Value) -> Result<Params> {
+        let params = sexpr.as_list()
+            .ok_or_else(|| ErrorKind::Type(format!("Expected parameter list, got: {:?}", sexpr)))?;
 
         let mut required_params = Vec::new();
         let mut optional_params = Vec::new();
         let mut keyword_params = HashMap::new();
         let mut rest_param = None;
 
-        let mut iter = params.into_iter();
+        for param in params {
+            if let Some(name) = param.as_keyword() {
+                keyword_params.insert(name, None);
+                continue;
+            } else if let Some((name, value)) = param.as_pair() {
+                if let Some(name) = name.as_keyword() {
+                    keyword_params.insert(name, Some(value));
+                    continue;
+                }
+            }
 
-        while let Some(param) = iter.next() {
             let (name, expr) = if let Some((symbol, value)) = param.as_symbol_value_pair() {
                 (symbol, Some(value))
             } else if let Some (symbol) = param.as_symbol() {
                 (symbol, None)
             } else {
-                panic!("Expected paramater or parameter and default value, got: {:?}", param);
+                return Err(ErrorKind::Type(
+                    format!("Expected paramater or parameter and default value, got: {:?}",
+                            param)).into());
             };
 
-            if name.starts_with(":") {
-                keyword_params.insert(name[1..].to_string(), expr);
-            } else if !keyword_params.is_empty() {
-                panic!("Keyword parameters must be defined after positional parameters");
-            } else if name.starts_with("...") {
+            if !keyword_params.is_empty() {
+                return Err(ErrorKind::Arity(
+                    "Keyword parameters must be defined after positional parameters"
+                        .to_string()).into());
+            } else if name.as_str().starts_with("...") {
                 if rest_param.is_some() {
-                    panic!("Only one rest parameter may be defined");
+                    return Err(ErrorKind::Arity(
+                        "Only one rest parameter may be defined".to_string()).into());
                 } else {
-                    rest_param = Some(name[3..].to_string());
+                    rest_param = Some(Symbol::intern(&name.as_str()[3..]));
                 }
             } else if rest_param.is_some() {
-                panic!("The rest parameter must be at the end of positional parameters");
+                return Err(ErrorKind::Arity(
+                    "The rest parameter must be at the end of positional parameters"
+                        .to_string()).into());
             } else if let Some(expr) = expr {
-                optional_params.push((name.to_string(), expr));
+                optional_params.push((name, expr));
             } else if !optional_params.is_empty() {
-                panic!("Optional parameters must be defined after optional parameters");
+                return Err(ErrorKind::Arity(
+                    "Optional parameters must be defined after optional parameters"
+                        .to_string()).into());
             } else {
-                required_params.push(name.to_string());
+                required_params.push(name);
             }
         }
 
-        Params {
+        Ok(Params {
             required_params,
             optional_params,
             keyword_params: keyword_params.into_iter().collect(),
             rest_param
-        }
+        })
     }
 
-    pub fn apply(&self, scope: &Rc<Scope>, args: Vec<Value>) {
+    fn has_keyword_param(&self, name: Symbol) -> bool {
+        self.keyword_params.iter().any(|(param, _)| *param == name)
+    }
+
+    pub fn apply(&self, scope: &Rc<Scope>, args: Vec<Value>) -> Result<()> {
         let mut iter = args.into_iter();
 
         let mut required_args: Vec<Value> = Vec::new();
         let mut optional_args: Vec<Value> = Vec::new();
-        let mut keyword_args: HashMap<String, Value> = HashMap::new();
+        let mut keyword_args: HashMap<Symbol, Value> = HashMap::new();
         let mut rest_args: Vec<Value> = Vec::new();
 
         while let Some(ref arg) = iter.next() {
-            if let Some(name) = arg.as_keyword_symbol() {
-                if keyword_args.contains_key(name) {
-                    panic!("Duplicate keyword argument: {}", name);
-                } else {
-                    let value = iter.next().expect("Keyword argument missing value");
-                    keyword_args.insert(name.to_string(), value);
+            // Keywords that don't name a keyword parameter are passed like
+            // any other value
+            let keyword = arg.as_keyword().filter(|&name| self.has_keyword_param(name));
+
+            if let Some(name) = keyword {
+                match keyword_args.entry(name) {
+                    Entry::Occupied(_) => {
+                        return Err(ErrorKind::Arity(
+                            format!("Duplicate keyword argument: {}", name)).into());
+                    },
+                    Entry::Vacant(entry) => {
+                        let value = iter.next()
+                            .ok_or_else(|| ErrorKind::Arity(
+                                format!("Keyword argument missing value: {}", name)))?;
+                        entry.insert(value);
+                    }
                 }
             } else if !keyword_args.is_empty() {
-                panic!("Unexpected value after keyword argument: {:?}", arg);
+                return Err(ErrorKind::Arity(
+                    format!("Unexpected value after keyword argument: {:?}", arg)).into());
             } else if required_args.len() < self.required_params.len() {
                 required_args.push(arg.clone());
             } else if optional_args.len() < self.optional_params.len() {
@@ -86,41 +120,43 @@ impl Params {
             } else if self.rest_param.is_some() {
                 rest_args.push(arg.clone());
             } else {
-                panic!("Unexpected additional argument: {:?}", arg);
+                return Err(ErrorKind::Arity(
+                    format!("Unexpected additional argument: {:?}", arg)).into());
             }
         }
 
         if required_args.len() < self.required_params.len() {
             let mut missing_params = self.required_params.iter().skip(required_args.len());
-            panic!("Missing required arguments: {}", missing_params.join(", "));
+            return Err(ErrorKind::Arity(
+                format!("Missing required arguments: {}", missing_params.join(", "))).into());
         }
 
         let optional_args_count = optional_args.len();
 
         for (name, value) in self.required_params.iter().zip(required_args) {
-            scope.set_value(name.to_string(), value.clone());
+            scope.set_value(*name, value.clone());
         }
 
         for (param, value) in self.optional_params.iter().zip(optional_args) {
             let (name, _expr) = param;
-            scope.set_value(name.to_string(), value.clone());
+            scope.set_value(*name, value.clone());
         }
 
         for (name, expr) in self.optional_params.iter().skip(optional_args_count) {
-            scope.set_value(name.to_string(), expr.clone().eval(scope));
+            scope.set_value(*name, expr.clone().eval(scope)?);
         }
 
         if let Some(ref rest_param) = self.rest_param {
-            scope.set_value(rest_param.clone(), Value::list(rest_args.into_iter()));
+            scope.set_value(*rest_param, Value::list(rest_args.into_iter()));
         }
 
         for (name, expr) in &self.keyword_params {
             if !keyword_args.contains_key(name) {
                 if let Some(expr) = expr {
-                    keyword_args.insert(name.to_string(),
-                                        expr.clone().eval(scope));
+                    keyword_args.insert(*name, expr.clone().eval(scope)?);
                 } else {
-                    panic!("Mising required keyword argument: {}", name);
+                    return Err(ErrorKind::Arity(format!("Missing required keyword argument: {}",
+                                                    name)).into());
                 }
             }
         }
@@ -128,5 +164,7 @@ impl Params {
         for (name, value) in keyword_args.into_iter() {
             scope.set_value(name, value);
         }
+
+        Ok(())
     }
 }
@@ -1,6 +1,11 @@
-use nom::{Needed, recognize_float, digit};
+use nom::{self, IResult, Needed, recognize_float, digit, multispace};
 use nom::types::CompleteStr;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
 use expr::Expr;
+use error::{Error, ErrorKind};
+use location::Source;
 
 named!(float<CompleteStr, f64>,
        flat_map!(call!(recognize_float), parse_to!(f64)));
@@ -22,6 +27,46 @@ named!(integer<CompleteStr, i64>, flat_map!(
     parse_to!(i64)
 ));
 
+// Integer literals too large for an `i64`.
+named!(big_integer<CompleteStr, BigInt>, flat_map!(
+    do_parse!(
+        res: recognize!(pair!(opt!(alt!(char!('+') | char!('-'))), digit)) >>
+            not!(one_of!(".eE")) >>
+            (res)
+    ),
+    parse_to!(BigInt)
+));
+
+// The error code for a ratio like `1/0`.
+const ZERO_DENOMINATOR: u32 = 1;
+
+named!(ratio_parts<CompleteStr, (BigInt, BigInt)>, separated_pair!(
+    flat_map!(recognize!(pair!(opt!(alt!(char!('+') | char!('-'))), digit)),
+              parse_to!(BigInt)),
+    char!('/'),
+    flat_map!(digit, parse_to!(BigInt))
+));
+
+// Ratios with a zero denominator fail outright, rather than being read as an
+// integer followed by a symbol.
+fn ratio(input: CompleteStr) -> IResult<CompleteStr, BigRational> {
+    let (rest, (numer, denom)) = ratio_parts(input)?;
+
+    if denom.is_zero() {
+        let error = error_position!(input, nom::ErrorKind::Custom(ZERO_DENOMINATOR));
+        return Err(nom::Err::Failure(error));
+    }
+
+    Ok((rest, BigRational::new(numer, denom)))
+}
+
+named!(number<CompleteStr, Expr>, alt!(
+    ratio       => { Expr::Ratio } |
+    integer     => { Expr::Integer } |
+    big_integer => { Expr::BigInt } |
+    float       => { Expr::Float }
+));
+
 named!(escaped_symbol<CompleteStr, String>, delimited!(
     char!('|'),
     escaped_transform!(is_not!("\\|"), '\\', take!(1)),
@@ -29,10 +74,21 @@ named!(escaped_symbol<CompleteStr, String>, delimited!(
 ));
 
 named!(simple_symbol<CompleteStr, String>,
-       escaped_transform!(is_not!(" \t\n\r\\\"'`()#|;"), '\\', take!(1)));
+       escaped_transform!(is_not!(" \t\n\r\\\"'`()[]{}#|;"), '\\', take!(1)));
 
 named!(symbol<CompleteStr, String>, alt!(escaped_symbol | simple_symbol));
 
+// Keywords are written as symbols prefixed with a colon, such as `:name`.
+fn keyword(input: CompleteStr) -> IResult<CompleteStr, String> {
+    let (rest, name) = preceded!(input, char!(':'), simple_symbol)?;
+
+    if name.is_empty() {
+        return Err(nom::Err::Error(error_position!(input, nom::ErrorKind::Custom(0))));
+    }
+
+    Ok((rest, name))
+}
+
 named!(string<CompleteStr, String>, delimited!(
     char!('"'),
     escaped_transform!(is_not!("\\\""), '\\', alt!(
@@ -48,60 +104,261 @@ named!(string<CompleteStr, String>, delimited!(
     char!('"')
 ));
 
-named!(sexpr<CompleteStr, Vec<Expr>>,
-       ws!(delimited!(char!('('), many0!(expr), char!(')'))));
+const CHARACTER_NAMES: &[(&str, char)] = &[
+    ("newline", '\n'),
+    ("space", ' '),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+    ("null", '\0'),
+    ("alarm", '\x07'),
+    ("backspace", '\x08'),
+    ("delete", '\x7f'),
+    ("escape", '\x1b')
+];
+
+/// The name a character is written with after `#\`, if it has one.
+pub fn character_name(c: char) -> Option<&'static str> {
+    CHARACTER_NAMES.iter().find(|&&(_, named)| named == c).map(|&(name, _)| name)
+}
+
+// Characters are written `#\a`, by name as in `#\newline`, or by code point
+// as in `#\u{1F600}`.
+fn character(input: CompleteStr) -> IResult<CompleteStr, char> {
+    let fail = || Err(nom::Err::Error(error_position!(input, nom::ErrorKind::Custom(0))));
+
+    if !input.starts_with("#\\") {
+        return fail();
+    }
+
+    let rest = &input.0[2..];
+
+    if rest.starts_with("u{") {
+        let code = rest.find('}')
+            .and_then(|end| u32::from_str_radix(&rest[2..end], 16).ok().map(|code| (code, end)))
+            .and_then(|(code, end)| ::std::char::from_u32(code).map(|c| (c, end)));
+
+        return match code {
+            Some((c, end)) => Ok((CompleteStr(&rest[end + 1..]), c)),
+            None => fail()
+        };
+    }
+
+    let word_len = rest.find(|c: char| !c.is_alphanumeric()).unwrap_or(rest.len());
 
-named!(quote<CompleteStr, Expr>, preceded!(char!('\''), expr));
+    if rest[..word_len].chars().count() > 1 {
+        return match CHARACTER_NAMES.iter().find(|&&(name, _)| name == &rest[..word_len]) {
+            Some(&(_, c)) => Ok((CompleteStr(&rest[word_len..]), c)),
+            None => fail()
+        };
+    }
 
-named!(backquote<CompleteStr, Expr>, preceded!(char!('`'), expr));
+    match rest.chars().next() {
+        Some(c) => Ok((CompleteStr(&rest[c.len_utf8()..]), c)),
+        None => fail()
+    }
+}
 
-named!(comma_list<CompleteStr, Expr>, do_parse!(
+// Parsers only see the input that remains, so those that need to know where
+// they are in the source are passed the length of the whole input.
+fn offset(input: CompleteStr, len: usize) -> IResult<CompleteStr, usize> {
+    Ok((input, len - input.len()))
+}
+
+named!(line_comment<CompleteStr, ()>,
+       value!((), preceded!(char!(';'), opt!(is_not!("\n")))));
+
+named!(block_comment<CompleteStr, ()>, value!((), delimited!(
+    tag!("#|"),
+    many0!(alt!(
+        block_comment |
+        value!((), preceded!(not!(tag!("|#")), take!(1)))
+    )),
+    tag!("|#")
+)));
+
+named_args!(datum_comment(len: usize)<CompleteStr, ()>,
+       value!((), preceded!(tag!("#;"), preceded!(call!(skip, len), call!(expr, len)))));
+
+// Whitespace and comments, allowed anywhere between expressions.
+named_args!(skip(len: usize)<CompleteStr, ()>, value!((), many0!(alt!(
+    value!((), multispace) |
+    line_comment |
+    block_comment |
+    call!(datum_comment, len)
+))));
+
+named_args!(sexpr(len: usize)<CompleteStr, Vec<Expr>>, delimited!(
+    char!('('),
+    preceded!(call!(skip, len), many0!(terminated!(call!(expr, len), call!(skip, len)))),
+    char!(')')
+));
+
+named_args!(vector(len: usize)<CompleteStr, Vec<Expr>>, delimited!(
+    char!('['),
+    preceded!(call!(skip, len), many0!(terminated!(call!(expr, len), call!(skip, len)))),
+    char!(']')
+));
+
+named_args!(map(len: usize)<CompleteStr, Vec<Expr>>, delimited!(
+    char!('{'),
+    preceded!(call!(skip, len), many0!(terminated!(call!(expr, len), call!(skip, len)))),
+    char!('}')
+));
+
+named_args!(quote(len: usize)<CompleteStr, Expr>,
+       preceded!(char!('\''), preceded!(call!(skip, len), call!(expr, len))));
+
+named_args!(backquote(len: usize)<CompleteStr, Expr>,
+       preceded!(char!('`'), preceded!(call!(skip, len), call!(expr, len))));
+
+named_args!(comma_list(len: usize)<CompleteStr, Expr>, do_parse!(
     char!(',') >>
         char!('@') >>
-        value: expr >>
+        call!(skip, len) >>
+        value: call!(expr, len) >>
         (value)
 ));
 
-named!(comma<CompleteStr, Expr>, do_parse!(
+named_args!(comma(len: usize)<CompleteStr, Expr>, do_parse!(
     char!(',') >>
         not!(char!('@')) >>
-        value: expr >>
+        call!(skip, len) >>
+        value: call!(expr, len) >>
         (value)
 ));
 
-named!(expr<CompleteStr, Expr>, alt!(
-    integer    => { |i| Expr::Integer(i) } |
-    float      => { |f| Expr::Float(f) } |
-    string     => { |s| Expr::String(s) } |
-    sexpr      => { |e| Expr::Sexpr(e) } |
-    quote      => { |e| Expr::quote(e) } |
-    backquote  => { |e| Expr::template(e) } |
-    comma      => { |e| Expr::TemplateExpr(Box::new(e)) } |
-    comma_list => { |e| Expr::TemplateListExpr(Box::new(e)) } |
-    symbol     => { |s| Expr::Symbol(s) }
+named_args!(expr(len: usize)<CompleteStr, Expr>, do_parse!(
+    offset: call!(offset, len) >>
+        expr: alt!(
+            number |
+            string                 => { Expr::String } |
+            character              => { Expr::Char } |
+            call!(sexpr, len)      => { |e| Expr::Sexpr(e, offset) } |
+            call!(vector, len)     => { Expr::Vector } |
+            call!(map, len)        => { Expr::Map } |
+            call!(quote, len)      => { |e| Expr::quote(e, offset) } |
+            call!(backquote, len)  => { |e| Expr::template(e, offset) } |
+            call!(comma, len)      => { |e| Expr::Unquote(Box::new(e)) } |
+            call!(comma_list, len) => { |e| Expr::UnquoteSplicing(Box::new(e)) } |
+            keyword                => { Expr::Keyword } |
+            symbol                 => { Expr::Symbol }
+        ) >>
+        (expr)
 ));
 
-named!(root<CompleteStr, Vec<Expr>>, ws!(many0!(expr)));
+named_args!(root(len: usize)<CompleteStr, Vec<Expr>>,
+       preceded!(call!(skip, len), many0!(terminated!(call!(expr, len), call!(skip, len)))));
+
+pub fn parse(string: &str) -> Result<Vec<Expr>, Error> {
+    parse_source(&Source::new(string, None))
+}
+
+pub fn parse_source(source: &Source) -> Result<Vec<Expr>, Error> {
+    let len = source.text.len();
 
-pub fn parse(string: &str) -> Result<Vec<Expr>, String> {
-    match root(CompleteStr(string)) {
+    match root(CompleteStr(source.text), len) {
         Ok((i, o)) => {
             if !i.is_empty() {
-                panic!("Expected EOF, got: {}", i);
+                let error: Error = ErrorKind::Parse(format!("Expected EOF, got: {}", i)).into();
+                Err(error.at(Some(source.locate(len - i.len()))))
             } else {
                 Ok(o)
             }
         },
+        Err(nom::Err::Failure(nom::Context::Code(i, nom::ErrorKind::Custom(ZERO_DENOMINATOR)))) => {
+            let error: Error = ErrorKind::Parse("Ratio with a zero denominator".to_string()).into();
+            Err(error.at(Some(source.locate(len - i.len()))))
+        },
         Err(e) => {
-            Err(format!("{}", e))
+            Err(ErrorKind::Parse(format!("{}", e)).into())
+        }
+    }
+}
+
+/// Reads `string` as a single number literal, giving `None` if anything else
+/// comes before or after it.
+pub fn parse_number(string: &str) -> Option<Expr> {
+    match number(CompleteStr(string)) {
+        Ok((rest, expr)) if rest.is_empty() => Some(expr),
+        _ => None
+    }
+}
+
+/// Whether `input` stops partway through an expression, such as inside an
+/// unclosed list or string, so that more input is needed to read it.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut chars = input.chars().peekable();
+    let mut depth = 0;
+    let mut block_comment_depth = 0;
+    // Set after a quote or datum comment prefix until its expression starts
+    let mut needs_expr = false;
+
+    while let Some(c) = chars.next() {
+        if block_comment_depth > 0 {
+            match (c, chars.peek()) {
+                ('|', Some('#')) => {
+                    chars.next();
+                    block_comment_depth -= 1;
+                },
+                ('#', Some('|')) => {
+                    chars.next();
+                    block_comment_depth += 1;
+                },
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match c {
+            '"' | '|' => {
+                loop {
+                    match chars.next() {
+                        Some('\\') => { chars.next(); },
+                        Some(end) if end == c => break,
+                        Some(_) => {},
+                        None => return true
+                    }
+                }
+
+                needs_expr = false;
+            },
+            ';' => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            },
+            '#' if chars.peek() == Some(&'|') => {
+                chars.next();
+                block_comment_depth += 1;
+            },
+            '#' if chars.peek() == Some(&';') => {
+                chars.next();
+                needs_expr = true;
+            },
+            '\'' | '`' | ',' => needs_expr = true,
+            '@' if needs_expr => {},
+            '(' | '[' | '{' => {
+                depth += 1;
+                needs_expr = false;
+            },
+            ')' | ']' | '}' => depth -= 1,
+            '\\' => {
+                chars.next();
+                needs_expr = false;
+            },
+            c if c.is_whitespace() => {},
+            _ => needs_expr = false
         }
     }
+
+    depth > 0 || block_comment_depth > 0 || needs_expr
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use nom::IResult;
 
     #[test]
     fn parse_integer() {
@@ -111,6 +368,78 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_big_integer() {
+        assert_eq!(
+            parse_to_strings("12345678901234567890 -98765432109876543210 1e3"),
+            vec!["12345678901234567890", "-98765432109876543210", "1000"]
+        );
+    }
+
+    #[test]
+    fn parse_ratio() {
+        assert_eq!(
+            parse_to_strings("1/3 -6/4 +8/2 a/2"),
+            vec!["1/3", "-3/2", "4", "a/2"]
+        );
+        assert_eq!(parse("1/0").map_err(|e| e.to_string()),
+                   Err("1:1: Parse error: Ratio with a zero denominator".to_string()));
+        assert!(parse("(+ 1 -00/000)").is_err());
+    }
+
+    #[test]
+    fn parse_single_number() {
+        assert_eq!(parse_number("-12"), Some(Expr::Integer(-12)));
+        assert_eq!(parse_number("2.5"), Some(Expr::Float(2.5)));
+        assert_eq!(parse_number("1/2").map(|e| e.to_string()), Some("1/2".to_string()));
+
+        for s in &["", "x", "1 2", " 1", "1)", "#;1 2", "1/0"] {
+            assert_eq!(parse_number(s), None);
+        }
+    }
+
+    #[test]
+    fn parse_vector() {
+        assert_eq!(
+            parse_to_strings("[1 [a \"b\"] ] [] (f [x])"),
+            vec!["[1 [a \"b\"]]", "[]", "(f [x])"]
+        );
+        assert!(parse("[1 2").is_err());
+        assert!(parse("[1 2)").is_err());
+    }
+
+    #[test]
+    fn parse_map() {
+        assert_eq!(
+            parse_to_strings("{:a 1 \"b\" [2]} {} (f {x y})"),
+            vec!["{:a 1 \"b\" [2]}", "{}", "(f {x y})"]
+        );
+        assert!(parse("{:a 1").is_err());
+        assert!(parse("{:a 1]").is_err());
+    }
+
+    #[test]
+    fn parse_char() {
+        assert_eq!(
+            parse_to_strings(concat!("#\\a #\\( #\\newline #\\space #\\u{1F600} #\\u ",
+                                     "#\\\u{e9} (#\\1 #\\))")),
+            vec!["#\\a", "#\\(", "#\\newline", "#\\space", "#\\\u{1F600}", "#\\u", "#\\\u{e9}",
+                 "(#\\1 #\\))"]
+        );
+        assert!(parse("#\\ab").is_err());
+        assert!(parse("#\\u{110000}").is_err());
+        assert!(parse("#\\").is_err());
+    }
+
+    #[test]
+    fn parse_keyword() {
+        let exprs = parse(":a |:b| : (:c)").unwrap();
+        assert_eq!(exprs[0], Expr::Keyword("a".to_string()));
+        assert_eq!(exprs[1], Expr::Symbol(":b".to_string()));
+        assert_eq!(exprs[2], Expr::Symbol(":".to_string()));
+        assert_eq!(exprs[3].to_string(), "(:c)");
+    }
+
     #[test]
     fn parse_float() {
         assert_eq!(
@@ -129,8 +458,9 @@ mod tests {
 
     #[test]
     fn parse_sexpr() {
+        let input = r#"(12.4 "string here\n")"#;
         assert_eq!(
-            sexpr(CompleteStr(r#"(12.4 "string here\n")"#)),
+            sexpr(CompleteStr(input), input.len()),
             Result::Ok((CompleteStr(""), vec![Expr::Float(12.4),
                                               Expr::String("string here\n".to_string())]))
         );
@@ -144,7 +474,59 @@ mod tests {
                                              Expr::String("{}".to_string()),
                                              Expr::Sexpr(vec![Expr::Symbol("+".to_string()),
                                                               Expr::Integer(1),
-                                                              Expr::Float(2.3)])])])
+                                                              Expr::Float(2.3)],
+                                                         14)],
+                                        0)])
+        );
+    }
+
+    #[test]
+    fn parse_offsets() {
+        assert_eq!(
+            parse("(a\n  (b) 'c)"),
+            Result::Ok(vec![Expr::Sexpr(vec![Expr::symbol("a"),
+                                             Expr::Sexpr(vec![Expr::symbol("b")], 5),
+                                             Expr::quote(Expr::symbol("c"), 9)],
+                                        0)])
+        );
+    }
+
+    fn parse_to_strings(string: &str) -> Vec<String> {
+        parse(string).unwrap().iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_line_comments() {
+        assert_eq!(
+            parse_to_strings("; leading comment\n\
+                              (a ; trailing comment\n\
+                               b);comment at end of input"),
+            vec!["(a b)"]
+        );
+    }
+
+    #[test]
+    fn parse_block_comments() {
+        assert_eq!(
+            parse_to_strings("#| block #| nested |# comment |#(a #|inner|# b)#||#"),
+            vec!["(a b)"]
+        );
+        assert!(parse("(a) #| unterminated #| nested |# comment").is_err());
+    }
+
+    #[test]
+    fn parse_datum_comments() {
+        assert_eq!(
+            parse_to_strings("#;(ignored (datum)) (a #; b c #;\n 'd) #;e"),
+            vec!["(a c)"]
+        );
+    }
+
+    #[test]
+    fn parse_comments_after_quote() {
+        assert_eq!(
+            parse_to_strings("' ; comment\n a `#| comment |# (b ,#;c d ,@ ;\n e)"),
+            vec!["'a", "(append (list 'b) (list d) e nil)"]
         );
     }
 
@@ -156,11 +538,40 @@ mod tests {
             Result::Ok(vec![Expr::Sexpr(
                 vec![Expr::symbol("append"),
                      Expr::Sexpr(vec![Expr::symbol("list"),
-                                      Expr::quote(Expr::symbol("println"))]),
+                                      Expr::quote(Expr::symbol("println"), 1)],
+                                 1),
                      Expr::Sexpr(vec![Expr::symbol("list"),
-                                      Expr::symbol("var")]),
-                     Expr::symbol("nil")]
+                                      Expr::symbol("var")],
+                                 1),
+                     Expr::symbol("nil")],
+                1
             )])
         );
     }
+
+    #[test]
+    fn incomplete_input() {
+        assert!(is_incomplete("(a (b)"));
+        assert!(is_incomplete("(a \"b)"));
+        assert!(is_incomplete("(a |b)"));
+        assert!(is_incomplete("'"));
+        assert!(is_incomplete("`(a ,@"));
+        assert!(is_incomplete("#| (a) #| |# |#  #| ("));
+        assert!(is_incomplete("(a ; b)\n"));
+        assert!(is_incomplete("#; ; comment\n"));
+        assert!(is_incomplete("(f [1 2"));
+        assert!(is_incomplete("{:a (b"));
+
+        assert!(!is_incomplete(""));
+        assert!(!is_incomplete("(a (b))"));
+        assert!(!is_incomplete("(a \"b)\\\"\")"));
+        assert!(!is_incomplete("(a |b)\\||)"));
+        assert!(!is_incomplete("(a b\\()"));
+        assert!(!is_incomplete("'a ; (\n"));
+        assert!(!is_incomplete("#| ( |# a"));
+        assert!(!is_incomplete("a)"));
+        assert!(!is_incomplete("[1 (a) [b]]"));
+        assert!(!is_incomplete("{:a [1] :b {}}"));
+        assert!(!is_incomplete("(list #\\( #\\\" #\\;)"));
+    }
 }
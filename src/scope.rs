@@ -2,54 +2,147 @@ use std::rc::Rc;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use value::{Value};
+use symbol::{Symbol, SymbolMap, SymbolSet};
+use convert::NativeFn;
+use error::{ErrorKind, Result};
+use limits::{Budget, Limits};
+use capability::Capability;
 use macros;
 use functions;
 
 #[derive(Debug, PartialEq)]
 pub struct Scope {
     parent: Option<Rc<Scope>>,
-    variables: RefCell<HashMap<String, Value>>
+    variables: RefCell<SymbolMap<Value>>,
+    frozen: RefCell<SymbolSet>,
+    budget: Rc<Budget>
 }
 
 impl Scope {
     pub fn root() -> Rc<Scope> {
+        Scope::root_with_limits(Limits::default())
+    }
+
+    /// A root scope whose evaluations are checked against `limits`.
+    pub fn root_with_limits(limits: Limits) -> Rc<Scope> {
+        Scope::root_with(Capability::ALL, limits)
+    }
+
+    /// A root scope with only the built-in functions from `capabilities`, so
+    /// that, for example, untrusted scripts can't touch the filesystem.
+    pub fn root_with(capabilities: &[Capability], limits: Limits) -> Rc<Scope> {
         let mut variables = HashMap::new();
 
-        macros::register(&mut variables);
-        functions::register(&mut variables);
+        for &capability in capabilities {
+            if capability == Capability::Core {
+                macros::register(&mut variables);
+            }
+
+            functions::register(&mut variables, capability);
+        }
+
+        let variables = variables.into_iter()
+            .map(|(name, value)| (Symbol::intern(&name), value))
+            .collect();
 
         Rc::new(Scope {
             parent: None,
-            variables: RefCell::new(variables)
+            variables: RefCell::new(variables),
+            frozen: RefCell::new(SymbolSet::default()),
+            budget: Rc::new(Budget::new(limits))
         })
     }
 
     pub fn push(self: Rc<Self>) -> Rc<Scope> {
         Rc::new(Scope {
+            budget: self.budget.clone(),
             parent: Some(self),
-            variables: RefCell::new(HashMap::new())
+            variables: RefCell::new(SymbolMap::default()),
+            frozen: RefCell::new(SymbolSet::default())
         })
     }
 
-    pub fn get_value(&self, symbol: &str) -> Value {
-        if symbol == "nil" {
-            Value::Nil
-        } else if symbol == "true" {
-            Value::Boolean(true)
-        } else if symbol == "false" {
-            Value::Boolean(false)
-        } else if symbol.starts_with(":") {
-            Value::Symbol(symbol.to_string())
-        } else if self.variables.borrow().contains_key(symbol) {
-            self.variables.borrow()[symbol].clone()
-        } else if let Some(ref parent) = self.parent {
-            parent.clone().get_value(symbol)
-        } else {
-            panic!("Symbol not found: {}", symbol);
+    /// The resources used by evaluations in this scope and the others that
+    /// share its root.
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    pub fn get_value(&self, symbol: Symbol) -> Result<Value> {
+        if symbol == Symbol::NIL {
+            return Ok(Value::Nil)
+        } else if symbol == Symbol::TRUE {
+            return Ok(Value::Boolean(true))
+        } else if symbol == Symbol::FALSE {
+            return Ok(Value::Boolean(false))
+        }
+
+        let mut scope = self;
+
+        loop {
+            if let Some(value) = scope.variables.borrow().get(&symbol) {
+                return Ok(value.clone());
+            }
+
+            match scope.parent {
+                Some(ref parent) => scope = parent,
+                None => return Err(ErrorKind::UnboundSymbol(symbol.to_string()).into())
+            }
         }
     }
 
-    pub fn set_value(&self, symbol: String, value: Value) {
+    pub fn set_value(&self, symbol: Symbol, value: Value) {
         self.variables.borrow_mut().insert(symbol, value);
     }
+
+    /// Binds a symbol on behalf of a script, which may not replace a frozen
+    /// binding.
+    pub fn define_value(&self, symbol: Symbol, value: Value) -> Result<()> {
+        self.check_not_frozen(symbol)?;
+        self.set_value(symbol, value);
+        Ok(())
+    }
+
+    /// Stops scripts from redefining or assigning to any of the symbols
+    /// currently bound in this scope, although they can still be shadowed by
+    /// local bindings and new symbols can still be defined.
+    pub fn freeze(&self) {
+        self.frozen.borrow_mut().extend(self.variables.borrow().keys().cloned());
+    }
+
+    fn check_not_frozen(&self, symbol: Symbol) -> Result<()> {
+        if self.frozen.borrow().contains(&symbol) {
+            Err(ErrorKind::Frozen(symbol.to_string()).into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Binds a Rust closure as a function, so it can capture state from the
+    /// application embedding the interpreter.
+    pub fn define_native<F>(&self, name: &str, func: F)
+        where F: Fn(Vec<Value>, Rc<Scope>) -> Result<Value> + 'static
+    {
+        self.set_value(Symbol::intern(name), Value::native_closure(name, func));
+    }
+
+    /// Binds a plain Rust function, whose arguments are converted from Lisp
+    /// values and whose result is converted back.
+    pub fn define_fn<Args, F: NativeFn<Args>>(&self, name: &str, func: F) {
+        let closure = func.into_closure(name);
+        self.set_value(Symbol::intern(name), Value::NativeClosure(Rc::new(closure)));
+    }
+
+    /// Updates an existing binding in the innermost scope that has one.
+    pub fn assign_value(&self, symbol: Symbol, value: Value) -> Result<()> {
+        if self.variables.borrow().contains_key(&symbol) {
+            self.check_not_frozen(symbol)?;
+            self.variables.borrow_mut().insert(symbol, value);
+            Ok(())
+        } else if let Some(ref parent) = self.parent {
+            parent.assign_value(symbol, value)
+        } else {
+            Err(ErrorKind::UnboundSymbol(symbol.to_string()).into())
+        }
+    }
 }
@@ -0,0 +1,128 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::{OnceLock, RwLock};
+
+/// An interned symbol name: a small integer standing for a string, so that
+/// symbols are cheap to copy, compare and hash. Names are interned for the
+/// life of the process, and shared by every interpreter, so scripts that
+/// create symbols from strings are charged for them as strings.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+// Symbols the interpreter looks for while evaluating, interned up front so
+// they can be compared without looking them up.
+const PREDEFINED: &[&str] = &["nil", "true", "false", "quote", "progn"];
+
+impl Symbol {
+    pub const NIL: Symbol = Symbol(0);
+    pub const TRUE: Symbol = Symbol(1);
+    pub const FALSE: Symbol = Symbol(2);
+    pub const QUOTE: Symbol = Symbol(3);
+    pub const PROGN: Symbol = Symbol(4);
+
+    pub fn intern(name: &str) -> Symbol {
+        if let Some(&symbol) = interner().read().unwrap().ids.get(name) {
+            return symbol;
+        }
+
+        let mut interner = interner().write().unwrap();
+
+        // Another thread may have interned it since it was looked up
+        if let Some(&symbol) = interner.ids.get(name) {
+            return symbol;
+        }
+
+        interner.add(name)
+    }
+
+    /// Whether a symbol with this name exists yet, so that the memory for a
+    /// new one can be accounted for before it's interned.
+    pub fn is_interned(name: &str) -> bool {
+        interner().read().unwrap().ids.contains_key(name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        interner().read().unwrap().names[self.0 as usize]
+    }
+}
+
+struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+    // Names are leaked so that they can be borrowed without holding the lock,
+    // which is fine since they're never removed.
+    names: Vec<&'static str>
+}
+
+impl Interner {
+    fn add(&mut self, name: &str) -> Symbol {
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name);
+        self.ids.insert(name, symbol);
+        symbol
+    }
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+
+    INTERNER.get_or_init(|| {
+        let mut interner = Interner { ids: HashMap::new(), names: Vec::new() };
+
+        for name in PREDEFINED {
+            interner.add(name);
+        }
+
+        RwLock::new(interner)
+    })
+}
+
+/// A map keyed by symbols, hashed by their IDs.
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+
+pub type SymbolSet = HashSet<Symbol, BuildHasherDefault<SymbolHasher>>;
+
+/// Hashes symbol IDs by multiplying them by a large odd constant, which
+/// spreads them over the whole hash without the cost of a hash function that
+/// resists collisions, since scripts can't choose IDs.
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(u64::from(byte));
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+impl<'a> From<&'a str> for Symbol {
+    fn from(name: &'a str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -1,35 +1,67 @@
 use scope::Scope;
-use std::ops::Add;
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::mem;
+use std::collections::{self, HashSet};
 use std::ops::Deref;
 use std::fmt;
-use function::{Function, Macro};
-
-#[derive(PartialEq, Clone)]
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use function::{Function, Macro, NativeClosure};
+use error::{ErrorKind, Frame, Result};
+use location::{self, Location};
+use symbol::Symbol;
+use hash_key::HashKey;
+use parser;
+use itertools::Itertools;
+
+#[derive(Clone)]
 pub enum Value {
     Float(f64),
     Integer(i64),
+    BigInt(BigInt),
+    Ratio(BigRational),
     Boolean(bool),
     String(String),
-    NativeFunction(String, fn(Vec<Value>) -> Value),
-    NativeMacro(String, fn(Vec<Rc<Value>>, Rc<Scope>) -> Value),
+    Char(char),
+    NativeFunction(String, fn(Vec<Value>, Rc<Scope>) -> Result<Value>),
+    NativeClosure(Rc<NativeClosure>),
+    NativeMacro(String, fn(Vec<Rc<Value>>, Rc<Scope>) -> Result<Tail>),
     Function(Rc<Function>),
     Macro(Rc<Macro>),
-    Symbol(String),
-    Cons(Rc<Value>, Rc<Value>),
+    Symbol(Symbol),
+    /// A symbol written with a leading colon, which evaluates to itself. The
+    /// colon isn't part of its name.
+    Keyword(Symbol),
+    Cons(Rc<Value>, Cdr),
+    /// A mutable array, shared by every copy of the value.
+    Vector(Rc<RefCell<Vec<Value>>>),
+    /// A mutable hash table, shared by every copy of the value.
+    HashMap(Rc<RefCell<collections::HashMap<HashKey, Value>>>),
     Nil
 }
 
+/// The result of applying a function or special form: either its value, or
+/// an expression in tail position that evaluates to its value.
+pub enum Tail {
+    Return(Value),
+    Eval(Rc<Value>, Rc<Scope>)
+}
+
 impl Value {
     pub fn as_list(&self) -> Option<Vec<Rc<Value>>> {
-        if let Value::Nil = self {
-            Some(Vec::new())
-        } else if let Value::Cons(left, right) = self {
-            let mut list = vec![left.clone()];
-            list.extend(right.as_list()?);
-            Some(list)
-        } else {
-            None
+        let mut list = Vec::new();
+        let mut next = self;
+
+        loop {
+            match next {
+                Value::Nil => return Some(list),
+                Value::Cons(left, right) => {
+                    list.push(left.clone());
+                    next = right;
+                },
+                _ => return None
+            }
         }
     }
 
@@ -37,16 +69,16 @@ impl Value {
         ConsIter::from_cons(self)
     }
 
-    pub fn as_symbol(&self) -> Option<&str> {
+    pub fn as_symbol(&self) -> Option<Symbol> {
         if let Value::Symbol(sym) = self {
-            Some(sym)
+            Some(*sym)
         } else {
             None
         }
     }
 
     pub fn as_pair(&self) -> Option<(Rc<Value>, Rc<Value>)> {
-        if let Value::Cons(left, right) = self.deref() {
+        if let Value::Cons(left, right) = self {
             if let Value::Cons(right, nil) = right.deref() {
                 if let Value::Nil = nil.deref() {
                     return Some((left.clone(), right.clone()));
@@ -57,12 +89,12 @@ impl Value {
         None
     }
 
-    pub fn as_symbol_value_pair(&self) -> Option<(&str, Rc<Value>)> {
+    pub fn as_symbol_value_pair(&self) -> Option<(Symbol, Rc<Value>)> {
         if let Value::Cons(left, right) = self {
             if let Value::Cons(right, nil) = right.deref() {
                 if let Value::Nil = nil.deref() {
                     if let Value::Symbol(symbol) = left.deref() {
-                        return Some((&symbol, right.clone()));
+                        return Some((*symbol, right.clone()));
                     }
                 }
             }
@@ -71,91 +103,251 @@ impl Value {
         None
     }
 
-    pub fn as_string(self) -> String {
+    pub fn as_string(self) -> Result<String> {
         match self {
-            Value::String(s) => s,
-            _ => panic!("Expected string, got: {:?}", self)
+            Value::String(s) => Ok(s),
+            _ => Err(ErrorKind::Type(format!("Expected string, got: {:?}", self)).into())
         }
     }
 
-    pub fn as_keyword_symbol(&self) -> Option<&str> {
-        if let Value::Symbol(sym) = self {
-            if sym.starts_with(":") {
-                return Some(&sym[1..]);
-            }
+    pub fn function_name(&self) -> Option<&str> {
+        use self::Value::*;
+
+        match self {
+            Function(func) => Some(func.name.as_ref().map_or("anonymous", |name| name)),
+            Macro(func) => Some(&func.name),
+            NativeClosure(func) => Some(&func.name),
+            NativeFunction(name, _) | NativeMacro(name, _) => Some(name),
+            _ => None
         }
+    }
 
-        None
+    pub fn as_keyword(&self) -> Option<Symbol> {
+        if let Value::Keyword(name) = self {
+            Some(*name)
+        } else {
+            None
+        }
+    }
+
+    pub fn keyword(name: &str) -> Value {
+        Value::Keyword(Symbol::intern(name))
+    }
+
+    pub fn cons(car: impl Into<Rc<Value>>, cdr: impl Into<Rc<Value>>) -> Value {
+        Value::Cons(car.into(), Cdr(cdr.into()))
     }
 
     pub fn progn(body: impl Into<Rc<Value>>) -> Value {
-        Value::Cons(Rc::new(Value::symbol("progn")),
-                    body.into())
+        Value::cons(Value::Symbol(Symbol::PROGN), body)
     }
 
     pub fn symbol(symbol: &str) -> Value {
-        Value::Symbol(symbol.to_string())
+        Value::Symbol(Symbol::intern(symbol))
     }
 
+    pub fn vector(values: Vec<Value>) -> Value {
+        Value::Vector(Rc::new(RefCell::new(values)))
+    }
+
+    pub fn hash_map(map: collections::HashMap<HashKey, Value>) -> Value {
+        Value::HashMap(Rc::new(RefCell::new(map)))
+    }
+
+    /// The entries of a hash table, sorted by key so that they're listed and
+    /// printed in a consistent order.
+    pub fn sorted_entries(map: &collections::HashMap<HashKey, Value>) -> Vec<(Value, Value)> {
+        map.iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .into_iter()
+            .map(|(key, value)| (key.to_value(), value.clone()))
+            .collect()
+    }
+
+    pub fn native_closure<F>(name: &str, func: F) -> Value
+        where F: Fn(Vec<Value>, Rc<Scope>) -> Result<Value> + 'static
+    {
+        Value::NativeClosure(Rc::new(NativeClosure::new(name, func)))
+    }
+
+
+    pub fn eval(&self, scope: &Rc<Scope>) -> Result<Value> {
+        let mut tail_call = None;
+
+        scope.budget().enter()
+            .and_then(|_depth| self.eval_tail_calls(scope, &mut tail_call))
+            .map_err(|e| {
+                let frame = tail_call.and_then(|(func, left)| {
+                    func.call_frame(location::lookup(&left))
+                });
+                let e = match frame {
+                    Some(frame) => e.in_frame(frame),
+                    None => e
+                };
+
+                match self {
+                    Value::Cons(left, _) => e.at(location::lookup(left)),
+                    _ => e
+                }
+            })
+    }
+
+    // Expressions in tail position are evaluated by looping rather than
+    // recursing, so that tail calls run in constant stack space. Only the
+    // innermost function entered by a tail call is kept for the backtrace,
+    // along with the form that called it, so that its frame only needs to be
+    // built if there's an error.
+    fn eval_tail_calls(&self, scope: &Rc<Scope>,
+                       tail_call: &mut Option<(Value, Rc<Value>)>) -> Result<Value> {
+        let mut tail = self.eval_step(scope, tail_call)?;
+
+        loop {
+            match tail {
+                Tail::Return(value) => return Ok(value),
+                Tail::Eval(expr, scope) => tail = expr.eval_step(&scope, tail_call)?
+            }
+        }
+    }
+
+    // Evaluates an expression up to the first expression in tail position.
+    fn eval_step(&self, scope: &Rc<Scope>,
+                 tail_call: &mut Option<(Value, Rc<Value>)>) -> Result<Tail> {
+        use self::Value::*;
+
+        scope.budget().step()?;
 
-    pub fn eval(&self, scope: &Rc<Scope>) -> Value {
         match self {
-            Value::Symbol(sym) => scope.get_value(sym),
-            Value::Cons(left, params) => {
-                let left = left.eval(scope);
+            Symbol(sym) => scope.get_value(*sym).map(Tail::Return),
+            Cons(left, params) => {
+                let func = left.eval(scope)
+                    .map_err(|e| e.at(location::lookup(left)))?;
+
+                let tail = func.apply(params.as_rc().clone(), scope)
+                    .map_err(|e| {
+                        let location = location::lookup(left);
+                        let e = e.at(location.clone());
+
+                        // Special forms without a location are ones the interpreter
+                        // wrapped around function bodies, not ones in the source.
+                        match (&func, func.call_frame(location.clone())) {
+                            (NativeMacro(..), _) if location.is_none() => e,
+                            (_, Some(frame)) => e.in_frame(frame),
+                            (_, None) => e
+                        }
+                    })?;
+
+                if let Tail::Eval(..) = tail {
+                    if let Function(_) | Macro(_) = func {
+                        *tail_call = Some((func, left.clone()));
+                    }
+                }
 
-                left.call(params.clone(), scope)
+                Ok(tail)
             },
-            _ => self.clone()
+            // Vector literals evaluate their elements into a new vector each
+            // time, so that mutating one doesn't change the literal.
+            Vector(values) => {
+                let values = values.borrow().iter()
+                    .map(|value| value.eval(scope))
+                    .collect::<Result<_>>()?;
+                Ok(Tail::Return(Value::vector(values)))
+            },
+            // As are hash table literals, with their keys, in the order
+            // they're printed in
+            HashMap(map) => {
+                let map = Value::sorted_entries(&map.borrow()).into_iter()
+                    .map(|(key, value)| {
+                        Ok((HashKey::from_value(key.eval(scope)?)?, value.eval(scope)?))
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(Tail::Return(Value::hash_map(map)))
+            },
+            _ => Ok(Tail::Return(self.clone()))
         }
     }
 
+    // The backtrace frame for a call to this function from `location`.
+    fn call_frame(&self, location: Option<Location>) -> Option<Frame> {
+        self.function_name().map(|name| Frame { name: name.to_string(), location })
+    }
 
-    pub fn call(&self, args: Rc<Value>, scope: &Rc<Scope>) -> Value {
+    pub fn call(&self, args: Rc<Value>, scope: &Rc<Scope>) -> Result<Value> {
+        match self.apply(args, scope)? {
+            Tail::Return(value) => Ok(value),
+            Tail::Eval(expr, scope) => expr.eval(&scope)
+        }
+    }
+
+    /// Calls a function with arguments that have already been evaluated, as
+    /// native functions taking other functions as arguments do.
+    pub fn invoke(&self, args: Vec<Value>, scope: &Rc<Scope>) -> Result<Value> {
+        use self::Value::*;
+
+        let result = match self {
+            NativeFunction(_name, func) => func(args, scope.clone()),
+            NativeClosure(func) => func.call(args, scope.clone()),
+            Function(func) => func.call(args),
+            _ => return Err(ErrorKind::Type(format!("Expected function, got: {:?}", self)).into())
+        };
+
+        result.map_err(|e| {
+            let name = self.function_name().unwrap_or("anonymous").to_string();
+            e.in_frame(Frame { name, location: None })
+        })
+    }
+
+    /// Calls a function, macro or special form with the unevaluated
+    /// arguments `args`, leaving any expression in tail position for the
+    /// caller to evaluate.
+    pub fn apply(&self, args: Rc<Value>, scope: &Rc<Scope>) -> Result<Tail> {
         use self::Value::*;
 
         match self {
-            Nil => panic!("Cannot call nil function"),
+            Nil => Err(ErrorKind::Type("Cannot call nil function".to_string()).into()),
             NativeFunction(_name, func) => {
                 let args = args.iter_cons()
-                    .map(|e| e.eval(&scope))
-                    .collect();
-                func(args)
+                    .map(|e| e?.eval(scope))
+                    .collect::<Result<_>>()?;
+                Ok(Tail::Return(func(args, scope.clone())?))
+            },
+            NativeClosure(func) => {
+                let args = args.iter_cons()
+                    .map(|e| e?.eval(scope))
+                    .collect::<Result<_>>()?;
+                Ok(Tail::Return(func.call(args, scope.clone())?))
             },
             NativeMacro(_name, func) => {
-                func(args.as_list().expect("Unable to evaluate improper list"),
-                     scope.clone())
+                let args = args.as_list()
+                    .ok_or_else(|| ErrorKind::Type("Unable to evaluate improper list"
+                                                   .to_string()))?;
+                func(args, scope.clone())
             },
             Function(func) => {
                 let args = args.iter_cons()
-                    .map(|e| e.eval(scope))
-                    .collect();
-                func.call(args)
+                    .map(|e| e?.eval(scope))
+                    .collect::<Result<_>>()?;
+                Ok(Tail::Eval(func.body(), func.bind(args)?))
             },
             Macro(func) => {
                 let args = args.iter_cons()
-                    .map(|v| v.deref().clone())
-                    .collect();
-                func.call(args).eval(scope)
+                    .map(|v| Ok(v?.deref().clone()))
+                    .collect::<Result<_>>()?;
+                Ok(Tail::Eval(Rc::new(func.call(args)?), scope.clone()))
             },
-            _ => panic!("Expected function")
+            _ => Err(ErrorKind::Type(format!("Expected function, got: {:?}", self)).into())
         }
     }
 
-    pub fn list(mut values: impl Iterator<Item=Value>) -> Value {
-        if let Some(value) = values.next() {
-            Value::Cons(Rc::new(value), Rc::new(Value::list(values)))
-        } else {
-            Value::Nil
-        }
+    pub fn list(values: impl Iterator<Item=Value>) -> Value {
+        Value::list_rc(values.map(Rc::new))
     }
 
-    pub fn list_rc(mut values: impl Iterator<Item=Rc<Value>>) -> Value {
-        if let Some(value) = values.next() {
-            Value::Cons(value, Rc::new(Value::list_rc(values)))
-        } else {
-            Value::Nil
-        }
+    pub fn list_rc(values: impl Iterator<Item=Rc<Value>>) -> Value {
+        // Built from the end so long lists don't need deep recursion
+        values.collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .fold(Value::Nil, |list, value| Value::cons(value, list))
     }
 }
 
@@ -171,6 +363,96 @@ impl From<Vec<Value>> for Value {
     }
 }
 
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        use self::Value::*;
+
+        match (self, other) {
+            (Float(a), Float(b)) => a == b,
+            (Integer(a), Integer(b)) => a == b,
+            (BigInt(a), BigInt(b)) => a == b,
+            (Ratio(a), Ratio(b)) => a == b,
+            (Boolean(a), Boolean(b)) => a == b,
+            (String(a), String(b)) => a == b,
+            (Char(a), Char(b)) => a == b,
+            // Compared by name as well as by address, so an address that
+            // isn't unique can't make two different builtins equal
+            (NativeFunction(a, f), NativeFunction(b, g)) => a == b && *f as usize == *g as usize,
+            (NativeMacro(a, f), NativeMacro(b, g)) => a == b && *f as usize == *g as usize,
+            // Functions are only equal to themselves, since comparing their
+            // scopes could lead back to the functions
+            (NativeClosure(a), NativeClosure(b)) => Rc::ptr_eq(a, b),
+            (Function(a), Function(b)) => Rc::ptr_eq(a, b),
+            (Macro(a), Macro(b)) => Rc::ptr_eq(a, b),
+            (Symbol(a), Symbol(b)) | (Keyword(a), Keyword(b)) => a == b,
+            (Cons(..), Cons(..)) => {
+                // Compared a cell at a time so long lists don't need deep recursion
+                let (mut a, mut b) = (self, other);
+
+                while let (Cons(a_car, a_cdr), Cons(b_car, b_cdr)) = (a, b) {
+                    if a_car != b_car {
+                        return false;
+                    }
+
+                    a = a_cdr;
+                    b = b_cdr;
+                }
+
+                a == b
+            },
+            (Vector(a), Vector(b)) => {
+                Rc::ptr_eq(a, b) || compare_once(a, b, || *a.borrow() == *b.borrow())
+            },
+            (HashMap(a), HashMap(b)) => {
+                Rc::ptr_eq(a, b) || compare_once(a, b, || *a.borrow() == *b.borrow())
+            },
+            (Nil, Nil) => true,
+            _ => false
+        }
+    }
+}
+
+thread_local! {
+    // Containers being printed or compared further up the stack. A container
+    // can contain itself, so these are checked to avoid recursing forever.
+    static PRINTING: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+    static COMPARING: RefCell<HashSet<(usize, usize)>> = RefCell::new(HashSet::new());
+}
+
+fn address<T>(container: &Rc<T>) -> usize {
+    Rc::as_ptr(container) as usize
+}
+
+// Writes a container, or a marker in its place if it's already being written
+// because it contains itself.
+fn write_once<T>(container: &Rc<T>, f: &mut fmt::Formatter,
+                 write: impl FnOnce(&mut fmt::Formatter) -> fmt::Result) -> fmt::Result {
+    let key = address(container);
+
+    if !PRINTING.with(|printing| printing.borrow_mut().insert(key)) {
+        return write!(f, "#<cycle>");
+    }
+
+    let result = write(f);
+    PRINTING.with(|printing| printing.borrow_mut().remove(&key));
+    result
+}
+
+// Compares two containers with `eq`, unless they're already being compared
+// further up the stack. Any difference will be found there, so they can be
+// treated as equal here.
+fn compare_once<T>(a: &Rc<T>, b: &Rc<T>, eq: impl FnOnce() -> bool) -> bool {
+    let key = (address(a), address(b));
+
+    if !COMPARING.with(|comparing| comparing.borrow_mut().insert(key)) {
+        return true;
+    }
+
+    let result = eq();
+    COMPARING.with(|comparing| comparing.borrow_mut().remove(&key));
+    result
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::Value::*;
@@ -178,9 +460,10 @@ impl fmt::Display for Value {
             Integer(n) => write!(f, "{}", n),
             Float(n) => write!(f, "{}", n),
             String(s) => write!(f, "{}", s),
+            Char(c) => write!(f, "{}", c),
             Boolean(b) => write!(f, "{}", b),
             Cons(left, right) => {
-                if *left.deref() == Value::symbol("quote") {
+                if *left.deref() == Value::Symbol(::symbol::Symbol::QUOTE) {
                     if let Some(list) = right.as_list() {
                         if list.len() == 1 {
                             return write!(f, "'{}", list[0]);
@@ -190,14 +473,14 @@ impl fmt::Display for Value {
 
                 write!(f, "({}", left)?;
 
-                let mut next = right.clone();
+                let mut next = right.as_rc().clone();
 
                 loop {
                     if let Value::Nil = next.clone().deref() {
                         break;
                     } else if let Value::Cons(left, right) = next.clone().deref() {
                         write!(f, " {}", left)?;
-                        next = right.clone();
+                        next = right.as_rc().clone();
                     } else {
                         write!(f, " . {}", next)?;
                         break;
@@ -206,6 +489,14 @@ impl fmt::Display for Value {
 
                 write!(f, ")")
             },
+            Vector(values) => write_once(values, f, |f| {
+                write!(f, "[{}]", values.borrow().iter().map(|value| value.to_string()).join(" "))
+            }),
+            HashMap(map) => write_once(map, f, |f| {
+                write!(f, "{{{}}}", Value::sorted_entries(&map.borrow()).iter()
+                       .map(|(key, value)| format!("{} {}", key, value))
+                       .join(" "))
+            }),
             _ => fmt::Debug::fmt(self, f)
         }
     }
@@ -216,12 +507,22 @@ impl fmt::Debug for Value {
         use self::Value::*;
         match self {
             Integer(n) => write!(f, "{:?}", n),
+            Value::BigInt(n) => write!(f, "{}", n),
+            Value::Ratio(n) => write!(f, "{}", n),
             Float(n) => write!(f, "{:?}", n),
             String(s) => write!(f, "{:?}", s),
+            Char(c) => {
+                match parser::character_name(*c) {
+                    Some(name) => write!(f, "#\\{}", name),
+                    None if c.is_control() => write!(f, "#\\u{{{:X}}}", *c as u32),
+                    None => write!(f, "#\\{}", c)
+                }
+            },
             Boolean(b) => write!(f, "{:?}", b),
             Symbol(s) => write!(f, "{}", s),
+            Keyword(name) => write!(f, ":{}", name),
             Cons(left, right) => {
-                if *left.deref() == Value::symbol("quote") {
+                if *left.deref() == Value::Symbol(::symbol::Symbol::QUOTE) {
                     if let Some(list) = right.as_list() {
                         if list.len() == 1 {
                             return write!(f, "'{:?}", list[0]);
@@ -231,14 +532,14 @@ impl fmt::Debug for Value {
 
                 write!(f, "({:?}", left)?;
 
-                let mut next = right.clone();
+                let mut next = right.as_rc().clone();
 
                 loop {
                     if let Value::Nil = next.clone().deref() {
                         break;
                     } else if let Value::Cons(left, right) = next.clone().deref() {
                         write!(f, " {:?}", left)?;
-                        next = right.clone();
+                        next = right.as_rc().clone();
                     } else {
                         write!(f, " . {:?}", next)?;
                         break;
@@ -247,89 +548,112 @@ impl fmt::Debug for Value {
 
                 write!(f, ")")
             },
+            Vector(values) => write_once(values, f, |f| {
+                write!(f, "[{}]", values.borrow().iter().map(|value| format!("{:?}", value))
+                       .join(" "))
+            }),
+            HashMap(map) => write_once(map, f, |f| {
+                write!(f, "{{{}}}", Value::sorted_entries(&map.borrow()).iter()
+                       .map(|(key, value)| format!("{:?} {:?}", key, value))
+                       .join(" "))
+            }),
             Nil => write!(f, "nil"),
-            Function(func) => write!(f, "<function {}>", func.name),
+            Function(func) => {
+                write!(f, "<function {}>", func.name.as_ref().map_or("anonymous", |name| name))
+            },
             Macro(func) => write!(f, "<macro {}>", func.name),
             NativeFunction(name, _) => write!(f, "<function {}>", name),
+            NativeClosure(func) => write!(f, "<function {}>", func.name),
             NativeMacro(name, _) => write!(f, "<macro {}>", name)
         }
     }
 }
 
-impl Add for Value {
-    type Output = Value;
+/// Iterates over the elements of a list, giving an error if it turns out to
+/// be improper.
+pub struct ConsIter {
+    list: Rc<Value>,
+    cons: Option<Rc<Value>>
+}
 
-    fn add(self, other: Value) -> Value {
-        use self::Value::*;
+impl ConsIter {
+    fn from_cons(value: Rc<Value>) -> Self {
+        ConsIter { list: value.clone(), cons: Some(value) }
+    }
+}
 
-        match (self, other) {
-            (Integer(a), Integer(b)) => Value::Integer(a + b),
-            (Integer(a), Float(b)) => Value::Float(a as f64 + b),
-            (Float(a), Integer(b)) => Value::Float(a + b as f64),
-            (Float(a), Float(b)) => Value::Float(a + b),
-            (a, b) => panic!("Unable to add {:?} and {:?}", a, b)
+impl Iterator for ConsIter {
+    type Item = Result<Rc<Value>>;
+
+    fn next(&mut self) -> Option<Result<Rc<Value>>> {
+        let cons = self.cons.take()?;
+
+        match cons.deref() {
+            Value::Cons(left, right) => {
+                self.cons = Some(right.as_rc().clone());
+                Some(Ok(left.clone()))
+            },
+            Value::Nil => None,
+            _ => Some(Err(ErrorKind::Type(format!("Not a proper list: {:?}", self.list)).into()))
         }
     }
 }
 
-pub trait Reduce<T> {
-    fn reduce<F>(self, f: F) -> Option<T>
-        where Self: Sized,
-              F: FnMut(T, T) -> T;
-}
+/// The rest of a list after the first element of a cons cell.
+///
+/// Lists are unlinked a cell at a time as they're dropped, rather than
+/// recursively, so that dropping a long one doesn't overflow the stack.
+#[derive(PartialEq, Clone)]
+pub struct Cdr(Rc<Value>);
 
-impl<T, I> Reduce<T> for I where I: Iterator<Item=T> {
-    #[inline]
-    fn reduce<F>(mut self, f: F) -> Option<T>
-        where Self: Sized,
-              F: FnMut(T, T) -> T,
-    {
-        self.next().map(|first| self.fold(first, f))
+impl Cdr {
+    pub fn as_rc(&self) -> &Rc<Value> {
+        &self.0
     }
-}
 
-pub struct ConsIter {
-    cons: Option<Rc<Value>>
-}
+    pub fn into_rc(mut self) -> Rc<Value> {
+        mem::replace(&mut self.0, Rc::new(Value::Nil))
+    }
 
-impl ConsIter {
-    fn from_cons(value: Rc<Value>) -> Self {
-        if let Value::Cons(_, _) = value.deref() {
-            ConsIter { cons: Some(value) }
-        } else if let Value::Nil = value.deref() {
-            ConsIter { cons: None }
+    // Whether the next cell of the list would be dropped along with this one.
+    fn has_unique_tail(&self) -> bool {
+        if let Value::Cons(..) = self.0.deref() {
+            Rc::strong_count(&self.0) == 1
         } else {
-            panic!("Not a list");
+            false
         }
     }
 }
 
-impl Iterator for ConsIter {
-    type Item = Rc<Value>;
-
-    // next() is the only required method
-    fn next(&mut self) -> Option<Rc<Value>> {
-        if let Some(cons) = self.cons.take() {
-            if let Value::Cons(left, right) = cons.deref() {
-                match right.deref() {
-                    Value::Cons(_, _) => {
-                        self.cons = Some(right.clone());
-                        Some(left.clone())
-                    },
-                    Value::Nil => {
-                        self.cons = None;
-                        Some(left.clone())
-                    },
-                    _ => {
-                        self.cons = None;
-                        Some(cons.clone())
-                    }
-                }
-            } else {
-                unreachable!("Should only be iterating cons");
+impl Deref for Cdr {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.0
+    }
+}
+
+impl From<Rc<Value>> for Cdr {
+    fn from(value: Rc<Value>) -> Self {
+        Cdr(value)
+    }
+}
+
+impl Drop for Cdr {
+    fn drop(&mut self) {
+        if !self.has_unique_tail() {
+            return;
+        }
+
+        let nil = Rc::new(Value::Nil);
+        let mut next = mem::replace(&mut self.0, nil.clone());
+
+        while let Ok(Value::Cons(_, mut cdr)) = Rc::try_unwrap(next) {
+            if !cdr.has_unique_tail() {
+                break;
             }
-        } else {
-            None
+
+            next = mem::replace(&mut cdr.0, nil.clone());
         }
     }
 }